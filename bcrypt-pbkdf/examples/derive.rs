@@ -0,0 +1,55 @@
+//! Derive a key with bcrypt_pbkdf.
+//!
+//! Usage: `derive <salt-hex> <rounds> <length>`, with the passphrase read
+//! from the first line of standard input. The key is printed in hex.
+extern crate bcrypt_pbkdf;
+
+use std::env;
+use std::io::{self, BufRead};
+use std::process;
+
+use bcrypt_pbkdf::Params;
+
+fn usage(msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    eprintln!("usage: derive <salt-hex> <rounds> <length> < passphrase");
+    process::exit(2);
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 3 {
+        usage("expected 3 arguments");
+    }
+    let salt = decode_hex(&args[0]).unwrap_or_else(|| usage("salt is not valid hex"));
+    let rounds: u32 = args[1].parse().unwrap_or_else(|_| usage("rounds is not a number"));
+    let len: usize = args[2].parse().unwrap_or_else(|_| usage("length is not a number"));
+
+    let mut passphrase = String::new();
+    if let Err(e) = io::stdin().lock().read_line(&mut passphrase) {
+        usage(&format!("could not read passphrase: {}", e));
+    }
+    let passphrase = passphrase.trim_end_matches(['\n', '\r']);
+
+    // `Params` rejects inputs which are almost certainly mistakes before
+    // spending time on the derivation.
+    match Params::new(rounds, len).derive(passphrase, &salt) {
+        Ok(key) => {
+            let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+            println!("{}", hex);
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
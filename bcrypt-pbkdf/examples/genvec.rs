@@ -0,0 +1,94 @@
+//! Generate a bcrypt_pbkdf test vector.
+//!
+//! Usage: `genvec <salt-hex> <rounds> <length>`, with the passphrase read
+//! from the first line of standard input. The vector is printed as an entry
+//! of the `tests()` list in `tests/lib.rs`, followed by a line with the same
+//! vector as a JSON object (in the format of `tests/data/openssh.json`,
+//! without the `cipher` field).
+extern crate bcrypt_pbkdf;
+extern crate serde;
+extern crate serde_json;
+
+use std::env;
+use std::io::{self, BufRead};
+use std::process;
+
+use bcrypt_pbkdf::bcrypt_pbkdf;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Vector<'a> {
+    passphrase: &'a str,
+    salt_hex: String,
+    rounds: u32,
+    out_len: usize,
+    expected_hex: String,
+}
+
+fn usage(msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    eprintln!("usage: genvec <salt-hex> <rounds> <length> < passphrase");
+    process::exit(2);
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Format `bytes` as the body of a `vec![...]`, 8 bytes per line.
+fn rust_bytes(bytes: &[u8]) -> String {
+    bytes.chunks(8)
+        .map(|line| {
+            let line: Vec<String> = line.iter().map(|b| format!("0x{:02x}", b)).collect();
+            format!("                {}", line.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 3 {
+        usage("expected 3 arguments");
+    }
+    let salt = decode_hex(&args[0]).unwrap_or_else(|| usage("salt is not valid hex"));
+    let rounds: u32 = args[1].parse().unwrap_or_else(|_| usage("rounds is not a number"));
+    if rounds == 0 {
+        usage("rounds must not be zero");
+    }
+    let len: usize = args[2].parse().unwrap_or_else(|_| usage("length is not a number"));
+
+    let mut passphrase = String::new();
+    if let Err(e) = io::stdin().lock().read_line(&mut passphrase) {
+        usage(&format!("could not read passphrase: {}", e));
+    }
+    let passphrase = passphrase.trim_end_matches(['\n', '\r']);
+
+    let mut out = vec![0u8; len];
+    bcrypt_pbkdf(passphrase, &salt, rounds, &mut out);
+
+    println!("        Test {{");
+    println!("            password: {:?},", passphrase);
+    println!("            salt: vec![\n{} ],", rust_bytes(&salt));
+    println!("            rounds: {},", rounds);
+    println!("            out: vec![\n{} ]", rust_bytes(&out));
+    println!("        }},");
+
+    let vector = Vector {
+        passphrase,
+        salt_hex: encode_hex(&salt),
+        rounds,
+        out_len: len,
+        expected_hex: encode_hex(&out),
+    };
+    println!("{}", serde_json::to_string(&vector).unwrap());
+}
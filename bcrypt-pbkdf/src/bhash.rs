@@ -0,0 +1,127 @@
+use core::convert::Infallible;
+
+use blowfish::Blowfish;
+use byteorder::{ByteOrder, LittleEndian};
+use crypto_mac::generic_array::typenum::U64;
+use sha2::Digest;
+
+pub(crate) const BHASH_WORDS: usize = 8;
+pub(crate) const BHASH_OUTPUT_SIZE: usize = BHASH_WORDS * 4;
+const BHASH_SEED: &[u8; BHASH_OUTPUT_SIZE] = b"OxychromaticBlowfishSwatDynamite";
+/// `BHASH_SEED` loaded as big-endian words, at compile time.
+pub(crate) const BHASH_SEED_WORDS: [u32; BHASH_WORDS] = seed_words(BHASH_SEED);
+
+// Known answer for the compile-time seed loading: "Oxyc" and "mite".
+const _: () = assert!(
+    BHASH_SEED_WORDS[0] == 0x4f78_7963 && BHASH_SEED_WORDS[7] == 0x6d69_7465);
+
+/// Load a `bhash` seed as the big-endian words which are encrypted.
+pub(crate) const fn seed_words(seed: &[u8; BHASH_OUTPUT_SIZE]) -> [u32; BHASH_WORDS] {
+    let mut words = [0u32; BHASH_WORDS];
+    let mut i = 0;
+    while i < BHASH_WORDS {
+        words[i] = u32::from_be_bytes(
+            [seed[4 * i], seed[4 * i + 1], seed[4 * i + 2], seed[4 * i + 3]]);
+        i += 1;
+    }
+    words
+}
+
+/// The bcrypt hash function used as the PRF of bcrypt_pbkdf.
+pub(crate) fn bhash(sha2_pass: &[u8], sha2_salt: &[u8]) -> [u8; BHASH_OUTPUT_SIZE] {
+    bhash_with_state(&mut Blowfish::bc_init_state(), &BHASH_SEED_WORDS, sha2_pass, sha2_salt)
+}
+
+/// `bhash` using `blowfish` as its working state and `seed` (see `seed_words`)
+/// as the data which is encrypted (`BHASH_SEED_WORDS` in standard
+/// bcrypt_pbkdf). `blowfish` must be in the initial state
+/// (`Blowfish::bc_init_state()`) and is left modified.
+pub(crate) fn bhash_with_state(
+    blowfish: &mut Blowfish, seed: &[u32; BHASH_WORDS], sha2_pass: &[u8],
+    sha2_salt: &[u8],
+) -> [u8; BHASH_OUTPUT_SIZE] {
+    bhash_with_rounds(blowfish, seed, sha2_pass, sha2_salt, 64, 64)
+}
+
+/// `bhash_with_state` with `key_rounds` iterations of the key expansion with
+/// the salt and the passphrase, and `enc_rounds` encryptions of the seed,
+/// instead of 64 of each.
+pub(crate) fn bhash_with_rounds(
+    blowfish: &mut Blowfish, seed: &[u32; BHASH_WORDS], sha2_pass: &[u8],
+    sha2_salt: &[u8], key_rounds: usize, enc_rounds: usize,
+) -> [u8; BHASH_OUTPUT_SIZE] {
+    blowfish.salted_expand_key(sha2_salt, sha2_pass);
+    for _ in 0..key_rounds {
+        blowfish.bc_expand_key(sha2_salt);
+        blowfish.bc_expand_key(sha2_pass);
+    }
+
+    let mut cdata = *seed;
+
+    // Each pair of words is read, encrypted and written back before the next
+    // pair, so the result does not depend on how the loops are scheduled.
+    for _ in 0..enc_rounds {
+        for i in (0..BHASH_WORDS).step_by(2) {
+            let [l, r] = blowfish.bc_encrypt([cdata[i], cdata[i + 1]]);
+            cdata[i] = l;
+            cdata[i + 1] = r;
+        }
+    }
+
+    let mut output = [0u8; BHASH_OUTPUT_SIZE];
+    LittleEndian::write_u32_into(&cdata, &mut output);
+    output
+}
+
+/// Compute the PBKDF2 block with the encoded block counter `counter` into
+/// `block`, where `prf` maps the `D` hash of a PRF input to the `bhash` of it.
+///
+/// `salted` is a `D` state which has already absorbed the salt. It is cloned
+/// rather than consumed, so that a derivation of several blocks only hashes
+/// the salt once and then feeds each block's 4-byte counter to a copy of it.
+///
+/// With the standard big-endian counter this is equivalent to the
+/// corresponding block of `pbkdf2::<Bhash<D>>`, but lets the caller control
+/// how each `bhash` is computed.
+pub(crate) fn pbkdf2_block<D, F>(
+    salted: &D, counter: &[u8; 4], rounds: u32, block: &mut [u8; BHASH_OUTPUT_SIZE], prf: F,
+)
+    where D: Digest<OutputSize = U64> + Clone, F: FnMut(&[u8]) -> [u8; BHASH_OUTPUT_SIZE]
+{
+    let result = pbkdf2_block_checked::<_, _, _, Infallible>(
+        salted, counter, rounds, block, prf, || Ok(()));
+    match result {
+        Ok(()) => {}
+        Err(never) => match never {},
+    }
+}
+
+/// `pbkdf2_block`, calling `check` before every `bhash` call and stopping
+/// with its error if it fails, in which case `block` is incomplete.
+///
+/// Every derivation goes through here, so this is where zero rounds are
+/// rejected: it panics if `rounds` is zero.
+pub(crate) fn pbkdf2_block_checked<D, F, C, E>(
+    salted: &D, counter: &[u8; 4], rounds: u32, block: &mut [u8; BHASH_OUTPUT_SIZE],
+    mut prf: F, mut check: C,
+) -> Result<(), E>
+    where D: Digest<OutputSize = U64> + Clone, F: FnMut(&[u8]) -> [u8; BHASH_OUTPUT_SIZE],
+          C: FnMut() -> Result<(), E>
+{
+    assert!(rounds > 0, "rounds must not be zero");
+    check()?;
+    let mut u = prf(&salted.clone().chain(counter).result());
+    *block = u;
+
+    let mut result = Ok(());
+    for _ in 1..rounds {
+        if let Err(e) = check() {
+            result = Err(e);
+            break;
+        }
+        u = prf(&D::digest(&u));
+        block.iter_mut().zip(u.iter()).for_each(|(a, b)| *a ^= b);
+    }
+    ::wipe(&mut u);
+    result
+}
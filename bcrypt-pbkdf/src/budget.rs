@@ -0,0 +1,89 @@
+#![cfg(feature="std")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use cancel::derive_interruptible;
+use cost::checked_estimate_ops;
+use errors::Error;
+use super::derive;
+
+/// The bcrypt_pbkdf function, giving up once it has run for longer than
+/// `max`.
+///
+/// The elapsed time is checked before every `bhash` call (each PBKDF2 round
+/// of each block), so the derivation stops at most one `bhash` after the
+/// budget runs out, however large `rounds` is. In that case `output` is
+/// zeroed and `Err(Error::TimeBudgetExceeded)` is returned. Zero rounds are
+/// rejected with `Err(Error::InvalidRounds)`. Otherwise the result is the
+/// same as `bcrypt_pbkdf`.
+pub fn bcrypt_pbkdf_budgeted(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8], max: Duration,
+) -> Result<(), Error> {
+    let start = Instant::now();
+    derive_interruptible(passphrase, salt, rounds, output, || {
+        if start.elapsed() > max {
+            Err(Error::TimeBudgetExceeded)?;
+        }
+        Ok(())
+    })
+}
+
+/// A pool of work, counted in Blowfish block encryptions like `estimate_ops`,
+/// shared by the derivations made with `derive_within_budget`.
+///
+/// This lets e.g. a server bound the total cost of the derivations it runs
+/// for untrusted requests, however many of them are made concurrently. The
+/// budget is only ever decreased by derivations; call `refill` to grant more
+/// work, e.g. periodically for rate limiting.
+#[derive(Debug)]
+pub struct WorkBudget {
+    remaining: AtomicU64,
+}
+
+impl WorkBudget {
+    /// Create a budget of `ops` Blowfish block encryptions.
+    pub fn new(ops: u64) -> WorkBudget {
+        WorkBudget { remaining: AtomicU64::new(ops) }
+    }
+
+    /// The work left in the budget.
+    pub fn remaining(&self) -> u64 {
+        self.remaining.load(Ordering::SeqCst)
+    }
+
+    /// Add `ops` to the budget, saturating at `u64::MAX`.
+    pub fn refill(&self, ops: u64) {
+        // The closure always returns `Some`, so this can not fail.
+        let _ = self.remaining.fetch_update(Ordering::SeqCst, Ordering::SeqCst,
+            |remaining| Some(remaining.saturating_add(ops)));
+    }
+
+    /// Take `ops` from the budget if that much is left.
+    fn reserve(&self, ops: u64) -> bool {
+        self.remaining.fetch_update(Ordering::SeqCst, Ordering::SeqCst,
+            |remaining| remaining.checked_sub(ops)).is_ok()
+    }
+}
+
+/// The bcrypt_pbkdf function, charging its cost to `budget`.
+///
+/// `estimate_ops(rounds, output.len())` is atomically taken from `budget`
+/// before deriving anything. If less than that is left (or the estimate does
+/// not fit in a `u64`), nothing is taken, `output` is left unchanged and
+/// `Err(Error::BudgetExhausted)` is returned. Zero rounds are rejected with
+/// `Err(Error::InvalidRounds)`, before taking anything. Otherwise the result
+/// is the same as `bcrypt_pbkdf`. The work is not returned to the budget
+/// afterwards.
+pub fn derive_within_budget(
+    budget: &WorkBudget, passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) -> Result<(), Error> {
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    let ops = checked_estimate_ops(rounds, output.len()).ok_or(Error::BudgetExhausted)?;
+    if !budget.reserve(ops) {
+        Err(Error::BudgetExhausted)?;
+    }
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+#![cfg(feature="std")]
+use std::vec::Vec;
+
+use errors::Error;
+use super::derive;
+
+/// A builder which accumulates the bcrypt_pbkdf inputs and derives a key of
+/// the requested length once all of them are set.
+///
+/// ```
+/// use bcrypt_pbkdf::BcryptPbkdfBuilder;
+///
+/// let key = BcryptPbkdfBuilder::new()
+///     .passphrase("password")
+///     .salt(b"salt")
+///     .rounds(4)
+///     .output_len(32)
+///     .build_and_derive()
+///     .unwrap();
+/// assert_eq!(key.len(), 32);
+/// ```
+#[derive(Clone, Default)]
+pub struct BcryptPbkdfBuilder<'a> {
+    passphrase: Option<&'a str>,
+    salt: Option<&'a [u8]>,
+    rounds: Option<u32>,
+    output_len: Option<usize>,
+}
+
+impl<'a> BcryptPbkdfBuilder<'a> {
+    /// Create a new builder with no fields set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the passphrase to process.
+    pub fn passphrase(mut self, passphrase: &'a str) -> Self {
+        self.passphrase = Some(passphrase);
+        self
+    }
+
+    /// Set the salt value.
+    pub fn salt(mut self, salt: &'a [u8]) -> Self {
+        self.salt = Some(salt);
+        self
+    }
+
+    /// Set the number of rounds to apply.
+    pub fn rounds(mut self, rounds: u32) -> Self {
+        self.rounds = Some(rounds);
+        self
+    }
+
+    /// Set the length of the derived key in bytes.
+    pub fn output_len(mut self, output_len: usize) -> Self {
+        self.output_len = Some(output_len);
+        self
+    }
+
+    /// Ask for the intermediate buffers (the passphrase hash and the
+    /// pre-transpose output) to be zeroized once the derivation is finished.
+    ///
+    /// With the `zeroize` feature they always are, as in every other function
+    /// of this crate, whatever is passed here. Without the feature this is a
+    /// no-op.
+    pub fn zeroize(self, _zeroize: bool) -> Self {
+        self
+    }
+
+    /// Derive the key, returning `Err(Error::MissingField)` naming the first
+    /// required field which was not set, or `Err(Error::InvalidRounds)` if
+    /// the number of rounds is zero.
+    pub fn build_and_derive(self) -> Result<Vec<u8>, Error> {
+        let passphrase = self.passphrase.ok_or(Error::MissingField("passphrase"))?;
+        let salt = self.salt.ok_or(Error::MissingField("salt"))?;
+        let rounds = self.rounds.ok_or(Error::MissingField("rounds"))?;
+        let output_len = self.output_len.ok_or(Error::MissingField("output_len"))?;
+        if rounds == 0 {
+            Err(Error::InvalidRounds)?;
+        }
+
+        let mut output = vec![0u8; output_len];
+        derive(passphrase.as_bytes(), salt, rounds, &mut output);
+        Ok(output)
+    }
+}
@@ -0,0 +1,67 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use byteorder::{ByteOrder, BigEndian};
+use sha2::{Digest, Sha512};
+
+use bhash::{bhash, pbkdf2_block_checked, BHASH_OUTPUT_SIZE};
+use errors::Error;
+use super::{stride, wipe};
+
+/// The bcrypt_pbkdf function, giving up as soon as `cancel` is set (e.g. by
+/// another thread, when the user cancels the operation).
+///
+/// `cancel` is checked before every `bhash` call (each PBKDF2 round of each
+/// block), so the derivation stops at most one `bhash` after it is set,
+/// however large `rounds` is. In that case `output` is zeroed and
+/// `Err(Error::Cancelled)` is returned. Zero rounds are rejected with
+/// `Err(Error::InvalidRounds)`. Otherwise the result is the same as
+/// `bcrypt_pbkdf`. It is also available in `no_std` builds.
+pub fn bcrypt_pbkdf_cancellable(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8], cancel: &AtomicBool,
+) -> Result<(), Error> {
+    derive_interruptible(passphrase, salt, rounds, output, || {
+        if cancel.load(Ordering::Relaxed) {
+            Err(Error::Cancelled)?;
+        }
+        Ok(())
+    })
+}
+
+/// Derive into `output`, calling `check` before every `bhash` call and
+/// stopping with its error (after zeroing `output`) if it fails. Returns
+/// `Err(Error::InvalidRounds)` without calling `check` if `rounds` is zero.
+pub(crate) fn derive_interruptible<F>(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8], mut check: F,
+) -> Result<(), Error>
+    where F: FnMut() -> Result<(), Error>
+{
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    let stride = stride(output.len());
+
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    let salted = Sha512::new().chain(salt);
+    let mut block = [0u8; BHASH_OUTPUT_SIZE];
+    let mut counter = [0u8; 4];
+    let mut result = Ok(());
+    for j in 0..stride {
+        BigEndian::write_u32(&mut counter, j as u32 + 1);
+        result = pbkdf2_block_checked(&salted, &counter, rounds, &mut block,
+            |sha2_salt| bhash(&sha2_pass, sha2_salt), &mut check);
+        if result.is_err() {
+            break;
+        }
+        // Block j supplies output bytes j, j + stride, j + 2 * stride, ...
+        for (out_byte, b) in output.iter_mut().skip(j).step_by(stride).zip(block.iter()) {
+            *out_byte = *b;
+        }
+    }
+    wipe(&mut sha2_pass);
+    wipe(&mut block);
+
+    if result.is_err() {
+        output.iter_mut().for_each(|b| *b = 0);
+    }
+    result
+}
@@ -0,0 +1,75 @@
+//! A C ABI for bcrypt_pbkdf, with the same checks as OpenSSH's
+//! `bcrypt_pbkdf()`.
+//!
+//! The function is exported unmangled as `bcrypt_pbkdf_c`. To link it into a
+//! C or C++ project, build a static library, e.g. with
+//! `cargo rustc --release --features capi --crate-type staticlib`, and
+//! declare it as:
+//!
+//! ```c
+//! int bcrypt_pbkdf_c(const uint8_t *pass, size_t pass_len,
+//!                    const uint8_t *salt, size_t salt_len, uint32_t rounds,
+//!                    uint8_t *out, size_t out_len);
+//! ```
+use core::ffi::c_int;
+use core::slice;
+
+use checked::checked_derive;
+use errors::Error;
+use params::DEFAULT_MAX_SALT_LEN;
+
+/// A pointer is null but its length is not zero.
+pub const BCRYPT_PBKDF_C_NULL_POINTER: c_int = -1;
+/// The passphrase is empty.
+pub const BCRYPT_PBKDF_C_EMPTY_PASSPHRASE: c_int = -2;
+/// The salt is empty or longer than `DEFAULT_MAX_SALT_LEN` bytes.
+pub const BCRYPT_PBKDF_C_INVALID_SALT_LEN: c_int = -3;
+/// The number of rounds is zero.
+pub const BCRYPT_PBKDF_C_INVALID_ROUNDS: c_int = -4;
+/// The output is empty or longer than `MAX_OUTPUT_LEN` bytes.
+pub const BCRYPT_PBKDF_C_INVALID_OUTPUT_LEN: c_int = -5;
+
+/// Derive `out_len` bytes into `out` with bcrypt_pbkdf, returning 0 on success
+/// or one of the negative `BCRYPT_PBKDF_C_*` codes if the inputs are invalid,
+/// in which case `out` is left unchanged.
+///
+/// As in OpenSSH, the passphrase and salt must not be empty, the salt must be
+/// at most 1 MiB, `rounds` must not be zero and `out_len` must be between 1
+/// and 1024. A pointer may be null if its length is zero (the corresponding
+/// input is then rejected as empty).
+///
+/// # Safety
+/// Each non-null pointer must be valid for reads (writes for `out`) of its
+/// length in bytes, and `out` must not overlap `pass` or `salt`.
+#[no_mangle]
+pub unsafe extern "C" fn bcrypt_pbkdf_c(
+    pass: *const u8, pass_len: usize, salt: *const u8, salt_len: usize, rounds: u32,
+    out: *mut u8, out_len: usize,
+) -> c_int {
+    if (pass.is_null() && pass_len != 0) || (salt.is_null() && salt_len != 0) ||
+        (out.is_null() && out_len != 0)
+    {
+        return BCRYPT_PBKDF_C_NULL_POINTER;
+    }
+    if pass_len == 0 {
+        return BCRYPT_PBKDF_C_EMPTY_PASSPHRASE;
+    }
+    if salt_len == 0 || salt_len > DEFAULT_MAX_SALT_LEN {
+        return BCRYPT_PBKDF_C_INVALID_SALT_LEN;
+    }
+    if rounds == 0 {
+        return BCRYPT_PBKDF_C_INVALID_ROUNDS;
+    }
+    if out_len == 0 {
+        return BCRYPT_PBKDF_C_INVALID_OUTPUT_LEN;
+    }
+
+    let pass = slice::from_raw_parts(pass, pass_len);
+    let salt = slice::from_raw_parts(salt, salt_len);
+    let out = slice::from_raw_parts_mut(out, out_len);
+    match checked_derive(pass, salt, rounds, out) {
+        Ok(()) => 0,
+        Err(Error::InvalidRounds) => BCRYPT_PBKDF_C_INVALID_ROUNDS,
+        Err(_) => BCRYPT_PBKDF_C_INVALID_OUTPUT_LEN,
+    }
+}
@@ -0,0 +1,176 @@
+#![cfg(feature="cipher")]
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::cipher::generic_array::GenericArray;
+use aes::{Aes128, Aes192, Aes256};
+use byteorder::{ByteOrder, BigEndian};
+use std::vec::Vec;
+
+use errors::Error;
+use openssh::{KdfOptions, SshCipher};
+use super::{derive, wipe};
+
+const AES_BLOCK_SIZE: usize = 16;
+
+/// How an OpenSSH cipher turns the first ciphertext block into plaintext.
+#[derive(Copy, Clone)]
+enum Mode {
+    /// Counter mode, with the IV as the initial counter block.
+    Ctr,
+    Cbc,
+    /// GCM: the first block is encrypted with counter `IV || 2`.
+    Gcm,
+}
+
+/// Key length, IV length and mode of the ciphers supported by
+/// `openssh_passphrase_correct`.
+fn cipher_params(cipher_name: &str) -> Option<(usize, usize, Mode)> {
+    let cipher = SshCipher::from_name(cipher_name)?;
+    let mode = match cipher {
+        SshCipher::Aes128Ctr | SshCipher::Aes192Ctr | SshCipher::Aes256Ctr => Mode::Ctr,
+        SshCipher::Aes128Cbc | SshCipher::Aes192Cbc | SshCipher::Aes256Cbc => Mode::Cbc,
+        SshCipher::Aes128Gcm | SshCipher::Aes256Gcm => Mode::Gcm,
+        SshCipher::Chacha20Poly1305 => return None,
+    };
+    Some((cipher.key_len(), cipher.iv_len(), mode))
+}
+
+/// Check whether `passphrase` decrypts an OpenSSH private key.
+///
+/// `cipher_name` and `kdf_options` are the key's cipher and bcrypt KDF
+/// options, and `encrypted` is its encrypted private key section (all of
+/// which `parse_openssh_kdf` returns). The key and IV are derived, the first
+/// block of `encrypted` is decrypted, and the two "checkint" values at its
+/// start are compared: they are equal only if the passphrase is correct.
+///
+/// The AES based ciphers (CTR, CBC and GCM modes) are supported; others
+/// return `Err(Error::UnsupportedCipher)`. The GCM tag is not checked.
+pub fn openssh_passphrase_correct(
+    passphrase: &str, cipher_name: &str, kdf_options: &KdfOptions,
+    encrypted: &[u8],
+) -> Result<bool, Error> {
+    let (check1, check2) = openssh_checkint_bytes(
+        passphrase, cipher_name, kdf_options, encrypted)?;
+    Ok(check1 == check2)
+}
+
+/// Decrypt the two "checkint" values at the start of an encrypted OpenSSH
+/// private key section, which are equal only if `passphrase` is correct.
+///
+/// This is the minimal work needed to check a passphrase: the key and IV are
+/// derived and a single cipher block is decrypted. `encrypted` only needs to
+/// hold the first 16 bytes of the section. The arguments and errors are
+/// those of `openssh_passphrase_correct`; `Err(Error::InvalidFormat)` is
+/// returned if `encrypted` is shorter than a block.
+pub fn openssh_checkint_bytes(
+    passphrase: &str, cipher_name: &str, kdf_options: &KdfOptions,
+    encrypted: &[u8],
+) -> Result<(u32, u32), Error> {
+    let (key_len, iv_len, mode) = cipher_params(cipher_name)
+        .ok_or(Error::UnsupportedCipher)?;
+    if encrypted.len() < AES_BLOCK_SIZE {
+        Err(Error::InvalidFormat)?;
+    }
+
+    let mut key_iv = [0u8; 32 + AES_BLOCK_SIZE];
+    let key_iv = &mut key_iv[..key_len + iv_len];
+    derive(passphrase.as_bytes(), &kdf_options.salt, kdf_options.rounds, key_iv);
+    let (key, iv) = key_iv.split_at(key_len);
+
+    let mut block = GenericArray::clone_from_slice(&encrypted[..AES_BLOCK_SIZE]);
+    match mode {
+        Mode::Ctr | Mode::Gcm => {
+            let mut counter = GenericArray::default();
+            counter[..iv_len].copy_from_slice(iv);
+            if let Mode::Gcm = mode {
+                counter[AES_BLOCK_SIZE - 1] = 2;
+            }
+            encrypt_block(key, &mut counter);
+            block.iter_mut().zip(counter.iter()).for_each(|(b, k)| *b ^= k);
+        }
+        Mode::Cbc => {
+            decrypt_block(key, &mut block);
+            block.iter_mut().zip(iv.iter()).for_each(|(b, v)| *b ^= v);
+        }
+    }
+    let checkints = (BigEndian::read_u32(&block[0..4]), BigEndian::read_u32(&block[4..8]));
+
+    wipe(key_iv);
+    wipe(&mut block);
+    Ok(checkints)
+}
+
+/// Decrypt the whole encrypted private key section of an OpenSSH private key
+/// (see `openssh_passphrase_correct` for the arguments). The GCM tag is not
+/// checked, and the result is garbage if the passphrase is wrong.
+pub(crate) fn decrypt_private_key(
+    passphrase: &str, cipher_name: &str, kdf_options: &KdfOptions,
+    encrypted: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let (key_len, iv_len, mode) = cipher_params(cipher_name)
+        .ok_or(Error::UnsupportedCipher)?;
+    if encrypted.is_empty() || !encrypted.len().is_multiple_of(AES_BLOCK_SIZE) {
+        Err(Error::InvalidFormat)?;
+    }
+
+    let mut key_iv = [0u8; 32 + AES_BLOCK_SIZE];
+    let key_iv = &mut key_iv[..key_len + iv_len];
+    derive(passphrase.as_bytes(), &kdf_options.salt, kdf_options.rounds, key_iv);
+    let (key, iv) = key_iv.split_at(key_len);
+
+    let mut plain = encrypted.to_vec();
+    match mode {
+        Mode::Ctr | Mode::Gcm => {
+            let mut counter: GenericArray<u8, _> = GenericArray::default();
+            counter[..iv_len].copy_from_slice(iv);
+            if let Mode::Gcm = mode {
+                counter[AES_BLOCK_SIZE - 1] = 2;
+            }
+            // CTR increments the whole block, GCM only its last 32 bits.
+            let counter_len = match mode {
+                Mode::Gcm => 4,
+                _ => AES_BLOCK_SIZE,
+            };
+            for chunk in plain.chunks_mut(AES_BLOCK_SIZE) {
+                let mut keystream = counter;
+                encrypt_block(key, &mut keystream);
+                chunk.iter_mut().zip(keystream.iter()).for_each(|(b, k)| *b ^= k);
+                for b in counter[AES_BLOCK_SIZE - counter_len..].iter_mut().rev() {
+                    *b = b.wrapping_add(1);
+                    if *b != 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        Mode::Cbc => {
+            let mut previous = GenericArray::clone_from_slice(iv);
+            for chunk in plain.chunks_mut(AES_BLOCK_SIZE) {
+                let ciphertext = GenericArray::clone_from_slice(chunk);
+                let mut block = ciphertext;
+                decrypt_block(key, &mut block);
+                block.iter_mut().zip(previous.iter()).for_each(|(b, v)| *b ^= v);
+                chunk.copy_from_slice(&block);
+                previous = ciphertext;
+            }
+        }
+    }
+
+    wipe(key_iv);
+    Ok(plain)
+}
+
+fn encrypt_block(key: &[u8], block: &mut GenericArray<u8, ::aes::cipher::consts::U16>) {
+    match key.len() {
+        16 => Aes128::new(GenericArray::from_slice(key)).encrypt_block(block),
+        24 => Aes192::new(GenericArray::from_slice(key)).encrypt_block(block),
+        _ => Aes256::new(GenericArray::from_slice(key)).encrypt_block(block),
+    }
+}
+
+fn decrypt_block(key: &[u8], block: &mut GenericArray<u8, ::aes::cipher::consts::U16>) {
+    match key.len() {
+        16 => Aes128::new(GenericArray::from_slice(key)).decrypt_block(block),
+        24 => Aes192::new(GenericArray::from_slice(key)).decrypt_block(block),
+        _ => Aes256::new(GenericArray::from_slice(key)).decrypt_block(block),
+    }
+}
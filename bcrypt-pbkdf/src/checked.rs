@@ -0,0 +1,87 @@
+use sha2::{Digest, Sha512};
+
+use bhash::BHASH_OUTPUT_SIZE;
+use errors::Error;
+use info::MAX_OUTPUT_LEN;
+use super::{generate, stride, transpose, wipe};
+
+/// The bcrypt_pbkdf function, returning an error instead of deriving from
+/// parameters OpenBSD's bcrypt_pbkdf would reject.
+///
+/// Returns `Err(Error::InvalidRounds)` if `rounds` is zero and
+/// `Err(Error::InvalidOutputLen)` if `output` is empty or longer than
+/// `MAX_OUTPUT_LEN` bytes. Otherwise the result is the same as
+/// `bcrypt_pbkdf`.
+///
+/// This function never panics and does not allocate: because the output
+/// length is bounded, the PBKDF2 output is built in a fixed-size buffer on the
+/// stack, and every index into it is below `32 * ceil(output.len() / 32)`.
+/// It is also available in `no_std` builds.
+///
+/// ```
+/// use bcrypt_pbkdf::{checked_bcrypt_pbkdf, Error};
+///
+/// // From the OpenBSD regression tests for bcrypt_pbkdf.
+/// let mut key = [0u8; 16];
+/// checked_bcrypt_pbkdf("password", b"salt", 42, &mut key).unwrap();
+/// assert_eq!(key, [
+///     0x83, 0x3c, 0xf0, 0xdc, 0xf5, 0x6d, 0xb6, 0x56,
+///     0x08, 0xe8, 0xf0, 0xdc, 0x0c, 0xe8, 0x82, 0xbd,
+/// ]);
+///
+/// assert_eq!(checked_bcrypt_pbkdf("password", b"salt", 0, &mut key),
+///     Err(Error::InvalidRounds));
+/// assert_eq!(checked_bcrypt_pbkdf("password", b"salt", 42, &mut []),
+///     Err(Error::InvalidOutputLen));
+/// ```
+pub fn checked_bcrypt_pbkdf(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) -> Result<(), Error> {
+    checked_derive(passphrase.as_bytes(), salt, rounds, output)
+}
+
+/// The smallest number of rounds OpenSSH accepts.
+pub const OPENSSH_MIN_ROUNDS: u32 = 1;
+
+/// The largest number of rounds OpenSSH accepts: `ssh-keygen -a` takes at most
+/// `INT_MAX` rounds. OpenSSH's `bcrypt_pbkdf()` itself only rejects zero.
+pub const OPENSSH_MAX_ROUNDS: u32 = i32::MAX as u32;
+
+/// The bcrypt_pbkdf function, returning an error instead of deriving from
+/// parameters OpenSSH would refuse.
+///
+/// Returns `Err(Error::RoundsOutOfRange)` if `rounds` is not between
+/// `OPENSSH_MIN_ROUNDS` and `OPENSSH_MAX_ROUNDS`, and otherwise behaves like
+/// `checked_bcrypt_pbkdf`. It is also available in `no_std` builds.
+pub fn bcrypt_pbkdf_openssh(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) -> Result<(), Error> {
+    if !(OPENSSH_MIN_ROUNDS..=OPENSSH_MAX_ROUNDS).contains(&rounds) {
+        Err(Error::RoundsOutOfRange)?;
+    }
+    checked_derive(passphrase.as_bytes(), salt, rounds, output)
+}
+
+/// `checked_bcrypt_pbkdf` for a passphrase which may not be valid UTF-8.
+pub(crate) fn checked_derive(
+    passphrase: &[u8], salt: &[u8], rounds: u32, output: &mut [u8],
+) -> Result<(), Error> {
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    if output.is_empty() || output.len() > MAX_OUTPUT_LEN {
+        Err(Error::InvalidOutputLen)?;
+    }
+
+    let stride = stride(output.len());
+    let mut buf = [0u8; MAX_OUTPUT_LEN];
+    let generated = &mut buf[..stride * BHASH_OUTPUT_SIZE];
+
+    let mut sha2_pass = Sha512::digest(passphrase);
+    generate::<Sha512>(&sha2_pass, salt, rounds, generated);
+    wipe(&mut sha2_pass);
+
+    transpose(generated, output);
+    wipe(generated);
+    Ok(())
+}
@@ -0,0 +1,145 @@
+#![cfg(feature="std")]
+use std::vec::Vec;
+
+use byteorder::{ByteOrder, BigEndian};
+use sha2::{Digest, Sha512};
+
+use bhash::BHASH_OUTPUT_SIZE;
+use errors::Error;
+use info::MAX_OUTPUT_LEN;
+use super::{generate_range, stride, transpose, wipe};
+
+/// Length of the fixed-size header of a serialized checkpoint: the rounds,
+/// the output length and the salt length.
+const HEADER_LEN: usize = 4 + 8 + 8;
+
+/// The state of a derivation which can be saved between its 32-byte PBKDF2
+/// blocks and resumed later, e.g. in another process.
+///
+/// Every block is independent of the others, so the state is just the
+/// parameters and the blocks completed so far. The passphrase is not part of
+/// it and must be given again to `resume`; resuming with a different
+/// passphrase silently produces a wrong key.
+///
+/// The completed blocks are key material: a serialized checkpoint must be
+/// stored as carefully as the derived key itself. They are wiped when the
+/// checkpoint is dropped.
+#[derive(Clone)]
+pub struct DerivationCheckpoint {
+    salt: Vec<u8>,
+    rounds: u32,
+    output_len: usize,
+    generated: Vec<u8>,
+}
+
+impl DerivationCheckpoint {
+    /// Start a derivation of `output_len` bytes; no block is computed yet.
+    pub fn new(salt: &[u8], rounds: u32, output_len: usize) -> DerivationCheckpoint {
+        DerivationCheckpoint {
+            salt: salt.to_vec(),
+            rounds,
+            output_len,
+            generated: Vec::new(),
+        }
+    }
+
+    /// The number of blocks computed so far.
+    pub fn completed_blocks(&self) -> usize {
+        self.generated.len() / BHASH_OUTPUT_SIZE
+    }
+
+    /// The number of blocks of the whole derivation, `ceil(output_len / 32)`.
+    pub fn total_blocks(&self) -> usize {
+        stride(self.output_len)
+    }
+
+    /// Compute at most `max_blocks` more blocks, returning the derived key
+    /// once all of them are done (it is then the same as the output of
+    /// `bcrypt_pbkdf`). Calling `resume` again after that returns the key
+    /// again without computing anything.
+    pub fn resume(&mut self, passphrase: &str, max_blocks: usize) -> Option<Vec<u8>> {
+        let first_block = self.completed_blocks();
+        let blocks = (self.total_blocks() - first_block).min(max_blocks);
+        if blocks > 0 {
+            let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+            let salted = Sha512::new().chain(&self.salt);
+            let start = self.generated.len();
+            let total_len = self.total_blocks() * BHASH_OUTPUT_SIZE;
+            if self.generated.capacity() < total_len {
+                // Move to a buffer for the whole output at once, wiping the
+                // old one, instead of letting the vector reallocate.
+                let mut generated = Vec::with_capacity(total_len);
+                generated.extend_from_slice(&self.generated);
+                wipe(&mut self.generated);
+                self.generated = generated;
+            }
+            self.generated.resize(start + blocks * BHASH_OUTPUT_SIZE, 0);
+            generate_range(&sha2_pass, &salted, self.rounds, first_block,
+                &mut self.generated[start..]);
+            wipe(&mut sha2_pass);
+        }
+
+        if self.completed_blocks() < self.total_blocks() {
+            return None;
+        }
+        let mut output = vec![0u8; self.output_len];
+        transpose(&self.generated, &mut output);
+        Some(output)
+    }
+
+    /// Serialize the checkpoint: the rounds (4 bytes), output length (8
+    /// bytes) and salt length (8 bytes) in big-endian order, then the salt and
+    /// the completed blocks.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; HEADER_LEN];
+        BigEndian::write_u32(&mut bytes[..4], self.rounds);
+        BigEndian::write_u64(&mut bytes[4..12], self.output_len as u64);
+        BigEndian::write_u64(&mut bytes[12..20], self.salt.len() as u64);
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(&self.generated);
+        bytes
+    }
+
+    /// Parse a checkpoint serialized with `to_bytes`.
+    ///
+    /// Returns `Err(Error::InvalidFormat)` if `bytes` is truncated, or does
+    /// not end with a whole number of blocks, at most `total_blocks`, and
+    /// `Err(Error::InvalidRounds)` if the number of rounds is zero. As the
+    /// bytes may come from untrusted storage, an output length over
+    /// `MAX_OUTPUT_LEN` is rejected with `Err(Error::InvalidOutputLen)`
+    /// rather than allocated.
+    pub fn from_bytes(bytes: &[u8]) -> Result<DerivationCheckpoint, Error> {
+        if bytes.len() < HEADER_LEN {
+            Err(Error::InvalidFormat)?;
+        }
+        let rounds = BigEndian::read_u32(&bytes[..4]);
+        let output_len = BigEndian::read_u64(&bytes[4..12]);
+        let salt_len = BigEndian::read_u64(&bytes[12..20]);
+        if rounds == 0 {
+            Err(Error::InvalidRounds)?;
+        }
+        if output_len > MAX_OUTPUT_LEN as u64 {
+            Err(Error::InvalidOutputLen)?;
+        }
+        if salt_len > (bytes.len() - HEADER_LEN) as u64 {
+            Err(Error::InvalidFormat)?;
+        }
+        let (output_len, salt_len) = (output_len as usize, salt_len as usize);
+        let (salt, generated) = bytes[HEADER_LEN..].split_at(salt_len);
+        if !generated.len().is_multiple_of(BHASH_OUTPUT_SIZE) ||
+            generated.len() / BHASH_OUTPUT_SIZE > stride(output_len)
+        {
+            Err(Error::InvalidFormat)?;
+        }
+
+        let mut checkpoint = DerivationCheckpoint::new(salt, rounds, output_len);
+        checkpoint.generated.extend_from_slice(generated);
+        Ok(checkpoint)
+    }
+}
+
+impl Drop for DerivationCheckpoint {
+    fn drop(&mut self) {
+        wipe(&mut self.generated);
+    }
+}
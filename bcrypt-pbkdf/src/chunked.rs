@@ -0,0 +1,42 @@
+#![cfg(feature="std")]
+use sha2::{Digest, Sha512};
+
+use bhash::BHASH_OUTPUT_SIZE;
+use super::{generate_range, stride, transpose, wipe};
+
+/// The bcrypt_pbkdf function, computing the PBKDF2 output `blocks_per_chunk`
+/// 32-byte blocks at a time instead of all at once, and calling
+/// `after_chunk` between the chunks.
+///
+/// Every block is independent of the others, so the output is identical to
+/// `bcrypt_pbkdf`; only the amount of work between two calls of
+/// `after_chunk` is bounded, to `blocks_per_chunk * rounds` `bhash` calls.
+/// `after_chunk` is given the number of blocks completed so far, and is
+/// called once per chunk, the last time with `ceil(output.len() / 32)`. It
+/// can e.g. let other work run, or report progress.
+///
+/// # Panics
+/// Panics if `blocks_per_chunk` is zero.
+pub fn bcrypt_pbkdf_chunked<F: FnMut(usize)>(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8], blocks_per_chunk: usize,
+    mut after_chunk: F,
+) {
+    assert!(blocks_per_chunk > 0, "blocks_per_chunk must not be zero");
+
+    let stride = stride(output.len());
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    let salted = Sha512::new().chain(salt);
+
+    let mut generated = vec![0u8; stride * BHASH_OUTPUT_SIZE];
+    let chunk_len = blocks_per_chunk.saturating_mul(BHASH_OUTPUT_SIZE);
+    let mut completed = 0;
+    for chunk in generated.chunks_mut(chunk_len) {
+        generate_range(&sha2_pass, &salted, rounds, completed, chunk);
+        completed += chunk.len() / BHASH_OUTPUT_SIZE;
+        after_chunk(completed);
+    }
+    transpose(&generated, output);
+
+    wipe(&mut sha2_pass);
+    wipe(&mut generated);
+}
@@ -0,0 +1,96 @@
+#[cfg(feature="std")]
+use std::time::{Duration, Instant};
+#[cfg(feature="std")]
+use std::vec::Vec;
+
+#[cfg(feature="std")]
+use sha2::{Digest, Sha512};
+
+#[cfg(feature="std")]
+use super::{derive_prehashed, wipe};
+use super::stride;
+
+/// Number of Blowfish block encryptions in one `bhash` call: 129 key
+/// expansions (the salted one, then 64 each with the salt and the passphrase)
+/// of 521 encryptions each, plus 64 passes over the 4 blocks of the seed.
+pub const BHASH_BLOWFISH_ENCRYPTIONS: u64 = 129 * 521 + 64 * 4;
+
+/// Cost of one PBKDF2-HMAC-SHA512 iteration in Blowfish block encryptions:
+/// two SHA-512 compressions (the inner and outer hashes, with the padded
+/// keys precomputed) of about 4 encryptions each.
+const PBKDF2_SHA512_ITERATION_ENCRYPTIONS: u64 = 8;
+
+/// Estimate the work of a bcrypt_pbkdf derivation as the number of Blowfish
+/// block encryptions it performs.
+///
+/// Each of the `ceil(output_len / 32)` PBKDF2 blocks calls `bhash` `rounds`
+/// times (zero rounds, which derivations reject, count as one), and each
+/// call performs `BHASH_BLOWFISH_ENCRYPTIONS` encryptions. The SHA-512
+/// hashes between the calls are not counted.
+///
+/// If the count does not fit in a `u64` (only possible for outputs of
+/// hundreds of terabytes), `u64::MAX` is returned instead.
+pub fn estimate_ops(rounds: u32, output_len: usize) -> u64 {
+    checked_estimate_ops(rounds, output_len).unwrap_or(u64::MAX)
+}
+
+/// `estimate_ops`, or `None` if the count does not fit in a `u64`.
+pub(crate) fn checked_estimate_ops(rounds: u32, output_len: usize) -> Option<u64> {
+    BHASH_BLOWFISH_ENCRYPTIONS
+        .checked_mul(u64::from(rounds.max(1)))
+        .and_then(|ops| ops.checked_mul(stride(output_len) as u64))
+}
+
+/// Estimate the PBKDF2-HMAC-SHA512 iteration count with about the same cost
+/// as bcrypt_pbkdf with `rounds` rounds, for an output of `output_len`
+/// bytes.
+///
+/// The model compares operation counts: the bcrypt_pbkdf work is
+/// `estimate_ops(rounds, output_len)` Blowfish encryptions, one SHA-512
+/// compression is counted as 4 Blowfish encryptions (their relative speed
+/// with the scalar implementations this crate uses on x86-64), and a
+/// PBKDF2-HMAC-SHA512 iteration costs two compressions for each of its
+/// `ceil(output_len / 64)` blocks. One `bhash` call is then worth about 8,400
+/// iterations.
+///
+/// The ratio is computed without intermediate overflow, so the estimate
+/// stays accurate for any `rounds` and `output_len` (it is saturated at
+/// `u64::MAX`, which is never reached in practice).
+///
+/// This only compares computation on a CPU. bcrypt_pbkdf's Blowfish state
+/// also makes it more expensive to attack with GPUs than PBKDF2, which this
+/// estimate does not account for.
+pub fn equivalent_pbkdf2_iterations(rounds: u32, output_len: usize) -> u64 {
+    let pbkdf2_blocks = output_len.div_ceil(64) as u128;
+    if pbkdf2_blocks == 0 {
+        return 0;
+    }
+    let ops = u128::from(BHASH_BLOWFISH_ENCRYPTIONS) * u128::from(rounds.max(1)) *
+        stride(output_len) as u128;
+    let iterations = ops / (u128::from(PBKDF2_SHA512_ITERATION_ENCRYPTIONS) * pbkdf2_blocks);
+    iterations.min(u128::from(u64::MAX)) as u64
+}
+
+/// Time one derivation of an `out_len`-byte key for each number of rounds in
+/// `rounds_list`, for building a table to choose the number of rounds from.
+///
+/// The passphrase is hashed once and shared by all the derivations, so the
+/// times only cover the derivations themselves. The result has one entry per
+/// element of `rounds_list`, in the same order. Each time comes from a single
+/// derivation on the current thread, so it is only as reliable as the
+/// machine is idle.
+#[cfg(feature="std")]
+pub fn time_rounds(
+    passphrase: &str, salt: &[u8], out_len: usize, rounds_list: &[u32],
+) -> Vec<(u32, Duration)> {
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    let mut output = vec![0u8; out_len];
+    let times = rounds_list.iter().map(|&rounds| {
+        let start = Instant::now();
+        derive_prehashed::<Sha512>(&sha2_pass, salt, rounds, &mut output);
+        (rounds, start.elapsed())
+    }).collect();
+    wipe(&mut output);
+    wipe(&mut sha2_pass);
+    times
+}
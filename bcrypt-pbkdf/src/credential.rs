@@ -0,0 +1,93 @@
+#![cfg(feature="rand_core")]
+use byteorder::{ByteOrder, BigEndian};
+use rand_core::RngCore;
+
+use errors::Error;
+use fixed::bcrypt_pbkdf_fixed;
+use verify::verify_fixed_len;
+
+/// The version of the `Credential` layout created by this crate.
+pub const CREDENTIAL_VERSION: u8 = 1;
+
+/// Length of a serialized `Credential`: the version, the rounds, the salt and
+/// the hash.
+pub const CREDENTIAL_LEN: usize = 1 + 4 + 16 + 32;
+
+/// A stored passphrase verifier: a 32-byte bcrypt_pbkdf hash with the random
+/// salt and the number of rounds it was derived with.
+///
+/// `to_bytes` packs it into `CREDENTIAL_LEN` bytes: the version, the rounds
+/// (big-endian), the salt and the hash.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Credential {
+    /// The layout version; always `CREDENTIAL_VERSION`.
+    pub version: u8,
+    /// The number of rounds.
+    pub rounds: u32,
+    /// The salt.
+    pub salt: [u8; 16],
+    /// The bcrypt_pbkdf output.
+    pub hash: [u8; 32],
+}
+
+impl Credential {
+    /// Create a credential for `passphrase`, with a salt drawn from `rng`.
+    ///
+    /// # Panics
+    /// Panics if `rounds` is zero.
+    pub fn create<R: RngCore>(rng: &mut R, passphrase: &str, rounds: u32) -> Credential {
+        assert!(rounds > 0, "rounds must not be zero");
+        let mut salt = [0u8; 16];
+        rng.fill_bytes(&mut salt);
+        Credential {
+            version: CREDENTIAL_VERSION,
+            rounds,
+            salt,
+            hash: bcrypt_pbkdf_fixed(passphrase.as_bytes(), &salt, rounds)
+                .expect("rounds is not zero"),
+        }
+    }
+
+    /// Check whether `passphrase` is the one the credential was created for.
+    ///
+    /// The hash is compared in constant time. Credentials of an unknown
+    /// version never verify.
+    pub fn verify(&self, passphrase: &str) -> bool {
+        self.version == CREDENTIAL_VERSION &&
+            bool::from(verify_fixed_len(passphrase, &self.salt, self.rounds, &self.hash))
+    }
+
+    /// Serialize the credential.
+    pub fn to_bytes(&self) -> [u8; CREDENTIAL_LEN] {
+        let mut bytes = [0u8; CREDENTIAL_LEN];
+        bytes[0] = self.version;
+        BigEndian::write_u32(&mut bytes[1..5], self.rounds);
+        bytes[5..21].copy_from_slice(&self.salt);
+        bytes[21..].copy_from_slice(&self.hash);
+        bytes
+    }
+
+    /// Parse a credential serialized with `to_bytes`.
+    ///
+    /// Returns `Err(Error::InvalidFormat)` if `bytes` is not `CREDENTIAL_LEN`
+    /// bytes long or has an unknown version, and `Err(Error::InvalidRounds)`
+    /// if the number of rounds is zero.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Credential, Error> {
+        if bytes.len() != CREDENTIAL_LEN || bytes[0] != CREDENTIAL_VERSION {
+            Err(Error::InvalidFormat)?;
+        }
+        let rounds = BigEndian::read_u32(&bytes[1..5]);
+        if rounds == 0 {
+            Err(Error::InvalidRounds)?;
+        }
+        let mut credential = Credential {
+            version: bytes[0],
+            rounds,
+            salt: [0; 16],
+            hash: [0; 32],
+        };
+        credential.salt.copy_from_slice(&bytes[5..21]);
+        credential.hash.copy_from_slice(&bytes[21..]);
+        Ok(credential)
+    }
+}
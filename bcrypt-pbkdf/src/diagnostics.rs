@@ -0,0 +1,49 @@
+#![cfg(feature="std")]
+use sha2::{Digest, Sha256};
+
+use super::{derive, stride};
+
+/// What `derive_debug` was given, for checking that a derivation's inputs
+/// are the intended ones (e.g. when its output differs from another tool's).
+///
+/// Nothing derived from the passphrase other than its length is recorded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DeriveDiagnostics {
+    /// Length of the passphrase, in bytes.
+    pub passphrase_len: usize,
+    /// Length of the salt, in bytes.
+    pub salt_len: usize,
+    /// SHA-256 hash of the salt, to compare salts without printing them.
+    pub salt_sha256: [u8; 32],
+    /// The number of rounds.
+    pub rounds: u32,
+    /// Length of the output, in bytes.
+    pub output_len: usize,
+    /// Number of 32-byte PBKDF2 blocks the output is made of.
+    pub stride: usize,
+}
+
+/// The bcrypt_pbkdf function, also returning a description of its inputs.
+///
+/// The derivation is the same as `bcrypt_pbkdf`. As this is meant for
+/// debugging, the description is only collected in builds with debug
+/// assertions enabled; otherwise it is `None`.
+pub fn derive_debug(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) -> Option<DeriveDiagnostics> {
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    if !cfg!(debug_assertions) {
+        return None;
+    }
+
+    let mut salt_sha256 = [0u8; 32];
+    salt_sha256.copy_from_slice(&Sha256::digest(salt));
+    Some(DeriveDiagnostics {
+        passphrase_len: passphrase.len(),
+        salt_len: salt.len(),
+        salt_sha256,
+        rounds,
+        output_len: output.len(),
+        stride: stride(output.len()),
+    })
+}
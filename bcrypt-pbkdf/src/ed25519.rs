@@ -0,0 +1,46 @@
+#![cfg(feature="cipher")]
+use check::decrypt_private_key;
+use errors::Error;
+use openssh::{ParsedKdf, Reader};
+use super::wipe;
+
+/// Decrypt an OpenSSH ed25519 private key and return its 32-byte private
+/// seed, e.g. to convert the key to an `age` identity.
+///
+/// Note that bcrypt_pbkdf does not derive the seed itself: it derives the key
+/// and IV of the cipher the private key section is encrypted with (48 bytes
+/// for `aes256-ctr`), and the seed is then read from the decrypted section.
+/// `key` is the output of `parse_openssh_kdf`, and the same ciphers as for
+/// `openssh_passphrase_correct` are supported.
+///
+/// Returns `Err(Error::IncorrectPassphrase)` if the check integers of the
+/// decrypted section differ, `Err(Error::UnsupportedKeyType)` if the (first)
+/// key is not an ed25519 key, and `Err(Error::InvalidFormat)` if the section
+/// can not be parsed. The GCM tag is not checked.
+pub fn openssh_ed25519_seed(passphrase: &str, key: &ParsedKdf) -> Result<[u8; 32], Error> {
+    let mut plain = decrypt_private_key(
+        passphrase, &key.cipher_name, &key.kdf_options, &key.encrypted_private_key)?;
+    let seed = read_ed25519_seed(&plain);
+    wipe(&mut plain);
+    seed
+}
+
+fn read_ed25519_seed(plain: &[u8]) -> Result<[u8; 32], Error> {
+    let mut reader = Reader(plain);
+    if reader.read_u32()? != reader.read_u32()? {
+        Err(Error::IncorrectPassphrase)?;
+    }
+    if reader.read_string()? != b"ssh-ed25519" {
+        Err(Error::UnsupportedKeyType)?;
+    }
+    // The public key, then the seed followed by the public key again.
+    let public = reader.read_string()?;
+    let private = reader.read_string()?;
+    if public.len() != 32 || private.len() != 64 || private[32..] != *public {
+        Err(Error::InvalidFormat)?;
+    }
+
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&private[..32]);
+    Ok(seed)
+}
@@ -0,0 +1,26 @@
+#![cfg(feature="std")]
+use super::{derive, wipe};
+
+/// Derive `total_len` bytes and pass them to `sink` in pieces of `chunk`
+/// bytes (the last piece may be shorter).
+///
+/// bcrypt_pbkdf interleaves every PBKDF2 block across the whole output, so
+/// the full key is derived before the first piece is emitted; only the
+/// delivery is incremental. The internal buffer is zeroized afterwards when
+/// the `zeroize` feature is enabled.
+///
+/// # Panics
+/// Panics if `chunk` is zero.
+pub fn bcrypt_pbkdf_emit<F>(
+    passphrase: &str, salt: &[u8], rounds: u32, total_len: usize, chunk: usize,
+    mut sink: F,
+)
+    where F: FnMut(&[u8])
+{
+    assert!(chunk > 0, "chunk size must be non-zero");
+
+    let mut output = vec![0u8; total_len];
+    derive(passphrase.as_bytes(), salt, rounds, &mut output);
+    output.chunks(chunk).for_each(&mut sink);
+    wipe(&mut output);
+}
@@ -0,0 +1,110 @@
+use core::fmt;
+#[cfg(feature="std")]
+use std::error;
+
+/// `bcrypt_pbkdf` error
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Error {
+    /// A required builder field was not set.
+    MissingField(&'static str),
+    /// The scratch buffer is too small for the requested output length.
+    ScratchTooSmall,
+    /// Two derivations of the same key produced different results.
+    ComputationMismatch,
+    /// The salt is longer than the configured maximum.
+    SaltTooLong,
+    /// The key uses a KDF other than bcrypt_pbkdf.
+    UnsupportedKdf,
+    /// The input could not be parsed.
+    InvalidFormat,
+    /// The key is encrypted with an unsupported cipher.
+    UnsupportedCipher,
+    /// The output buffer does not have the expected length.
+    LengthMismatch {
+        /// The expected length.
+        expected: usize,
+        /// The actual length.
+        got: usize,
+    },
+    /// The salt is made only of zero bytes.
+    AllZeroSalt,
+    /// The passphrase is empty.
+    EmptyPassphrase,
+    /// The number of rounds is zero.
+    InvalidRounds,
+    /// The output length is zero or greater than `MAX_OUTPUT_LEN`.
+    InvalidOutputLen,
+    /// The derivation took longer than its time budget.
+    TimeBudgetExceeded,
+    /// The passphrase is longer than the configured maximum.
+    PassphraseTooLong,
+    /// The derivation was cancelled.
+    Cancelled,
+    /// The passphrase does not decrypt the key.
+    IncorrectPassphrase,
+    /// The key is of an unsupported type.
+    UnsupportedKeyType,
+    /// The number of rounds is outside the range OpenSSH accepts.
+    RoundsOutOfRange,
+    /// The work budget does not cover the derivation.
+    BudgetExhausted,
+    /// A `SaltSource` could not provide the salt.
+    SaltUnavailable,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::MissingField(field) =>
+                write!(f, "missing required field `{}`", field),
+            Error::ScratchTooSmall => f.write_str("scratch buffer too small"),
+            Error::ComputationMismatch => f.write_str("computation mismatch"),
+            Error::SaltTooLong => f.write_str("salt too long"),
+            Error::UnsupportedKdf => f.write_str("unsupported KDF"),
+            Error::InvalidFormat => f.write_str("invalid format"),
+            Error::UnsupportedCipher => f.write_str("unsupported cipher"),
+            Error::LengthMismatch { expected, got } =>
+                write!(f, "output length mismatch: expected {}, got {}", expected, got),
+            Error::AllZeroSalt => f.write_str("salt is all zero bytes"),
+            Error::EmptyPassphrase => f.write_str("empty passphrase"),
+            Error::InvalidRounds => f.write_str("invalid number of rounds"),
+            Error::InvalidOutputLen => f.write_str("invalid output length"),
+            Error::TimeBudgetExceeded => f.write_str("time budget exceeded"),
+            Error::PassphraseTooLong => f.write_str("passphrase too long"),
+            Error::Cancelled => f.write_str("cancelled"),
+            Error::IncorrectPassphrase => f.write_str("incorrect passphrase"),
+            Error::UnsupportedKeyType => f.write_str("unsupported key type"),
+            Error::RoundsOutOfRange => f.write_str("number of rounds out of range"),
+            Error::BudgetExhausted => f.write_str("work budget exhausted"),
+            Error::SaltUnavailable => f.write_str("salt unavailable"),
+        }
+    }
+}
+
+#[cfg(feature="std")]
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::MissingField(_) => "missing required field",
+            Error::ScratchTooSmall => "scratch buffer too small",
+            Error::ComputationMismatch => "computation mismatch",
+            Error::SaltTooLong => "salt too long",
+            Error::UnsupportedKdf => "unsupported KDF",
+            Error::InvalidFormat => "invalid format",
+            Error::UnsupportedCipher => "unsupported cipher",
+            Error::LengthMismatch { .. } => "output length mismatch",
+            Error::AllZeroSalt => "salt is all zero bytes",
+            Error::EmptyPassphrase => "empty passphrase",
+            Error::InvalidRounds => "invalid number of rounds",
+            Error::InvalidOutputLen => "invalid output length",
+            Error::TimeBudgetExceeded => "time budget exceeded",
+            Error::PassphraseTooLong => "passphrase too long",
+            Error::Cancelled => "cancelled",
+            Error::IncorrectPassphrase => "incorrect passphrase",
+            Error::UnsupportedKeyType => "unsupported key type",
+            Error::RoundsOutOfRange => "number of rounds out of range",
+            Error::BudgetExhausted => "work budget exhausted",
+            Error::SaltUnavailable => "salt unavailable",
+        }
+    }
+}
@@ -0,0 +1,37 @@
+#![cfg(all(feature="hkdf", feature="std"))]
+use std::vec::Vec;
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use super::{derive, wipe};
+
+/// Length of the bcrypt_pbkdf master secret expanded by
+/// `bcrypt_pbkdf_then_hkdf`.
+const MASTER_LEN: usize = 32;
+
+/// Derive a 32-byte master secret with bcrypt_pbkdf and expand it to
+/// `out_len` bytes with HKDF-Expand (SHA-256) using `info`.
+///
+/// The cost of bcrypt_pbkdf grows with the output length, so this is a much
+/// cheaper way to obtain a lot of key material (e.g. several subkeys with
+/// distinct `info` values). The master secret is used directly as the HKDF
+/// pseudorandom key, without an extract step. The result is *not* the same
+/// as `bcrypt_pbkdf` with a longer output.
+///
+/// # Panics
+/// Panics if `out_len` is greater than 8160 (255 SHA-256 outputs), the
+/// HKDF-Expand limit.
+pub fn bcrypt_pbkdf_then_hkdf(
+    passphrase: &str, salt: &[u8], rounds: u32, info: &[u8], out_len: usize,
+) -> Vec<u8> {
+    let mut master = [0u8; MASTER_LEN];
+    derive(passphrase.as_bytes(), salt, rounds, &mut master);
+
+    let hkdf = Hkdf::<Sha256>::from_prk(&master).expect("PRK is the SHA-256 output size");
+    wipe(&mut master);
+
+    let mut output = vec![0u8; out_len];
+    hkdf.expand(info, &mut output).expect("out_len is at most 8160");
+    output
+}
@@ -0,0 +1,82 @@
+#[cfg(feature="heapless")]
+use heapless::Vec;
+use sha2::{Digest, Sha512};
+
+use bhash::{bhash, BHASH_OUTPUT_SIZE};
+use errors::Error;
+use super::{derive_blockwise, generate, stride, transpose, wipe};
+
+/// The bcrypt_pbkdf function for a fixed output length and 16-byte salt,
+/// using only the stack.
+///
+/// The output is produced one 32-byte PBKDF2 block at a time, each block
+/// being scattered directly to its positions in the output, so no grid buffer
+/// is needed and the working memory does not depend on `OUT_LEN`. This
+/// function does not allocate and does not panic: zero rounds are rejected
+/// with `Err(Error::InvalidRounds)`.
+///
+/// Unlike `bcrypt_pbkdf`, the passphrase is taken as raw bytes; the result
+/// for a UTF-8 passphrase is the same.
+pub fn bcrypt_pbkdf_fixed<const OUT_LEN: usize>(
+    passphrase: &[u8], salt: &[u8; 16], rounds: u32,
+) -> Result<[u8; OUT_LEN], Error> {
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    let mut output = [0u8; OUT_LEN];
+    let mut sha2_pass = Sha512::digest(passphrase);
+    derive_blockwise(salt, rounds, &mut output, |sha2_salt| bhash(&sha2_pass, sha2_salt));
+    wipe(&mut sha2_pass);
+    Ok(output)
+}
+
+/// The bcrypt_pbkdf function returning its `N`-byte output in a
+/// `heapless::Vec`, for targets without a global allocator.
+///
+/// As with `bcrypt_pbkdf_fixed`, the output is produced one 32-byte PBKDF2
+/// block at a time, so apart from the returned vector only a fixed amount of
+/// stack is used, whatever `N` is. The passphrase is taken as raw bytes and
+/// the salt may have any length; the result is the same as `bcrypt_pbkdf`.
+#[cfg(feature="heapless")]
+pub fn bcrypt_pbkdf_heapless<const N: usize>(
+    passphrase: &[u8], salt: &[u8], rounds: u32,
+) -> Vec<u8, N> {
+    let mut output = Vec::new();
+    output.resize(N, 0).expect("N is the capacity");
+    let mut sha2_pass = Sha512::digest(passphrase);
+    derive_blockwise(salt, rounds, &mut output, |sha2_salt| bhash(&sha2_pass, sha2_salt));
+    wipe(&mut sha2_pass);
+    output
+}
+
+/// The bcrypt_pbkdf function for an output length only known at runtime,
+/// using caller-provided memory instead of the heap.
+///
+/// The PBKDF2 output is computed in `scratch`, which must be at least
+/// `32 * ceil(output.len() / 32)` bytes long, otherwise
+/// `Err(Error::ScratchTooSmall)` is returned without deriving anything. Its
+/// contents are overwritten, and it may have any alignment. Otherwise the
+/// result is the same as `bcrypt_pbkdf`. This function does not allocate and
+/// is also available in `no_std` builds.
+///
+/// Returns `Err(Error::InvalidRounds)` if `rounds` is zero, before `scratch`
+/// or `output` is touched.
+pub fn bcrypt_pbkdf_in_place(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8], scratch: &mut [u8],
+) -> Result<(), Error> {
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    let stride = stride(output.len());
+    if scratch.len() < stride * BHASH_OUTPUT_SIZE {
+        Err(Error::ScratchTooSmall)?;
+    }
+    let generated = &mut scratch[..stride * BHASH_OUTPUT_SIZE];
+
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    generate::<Sha512>(&sha2_pass, salt, rounds, generated);
+    wipe(&mut sha2_pass);
+    transpose(generated, output);
+    wipe(generated);
+    Ok(())
+}
@@ -0,0 +1,26 @@
+/// The largest output length, in bytes, accepted by OpenBSD's bcrypt_pbkdf.
+pub const MAX_OUTPUT_LEN: usize = 1024;
+
+/// The configuration this crate was built with, as returned by `info`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CrateInfo {
+    /// The crate version.
+    pub version: &'static str,
+    /// The Blowfish implementation in use. This is currently always
+    /// `"scalar"`; there is no SIMD backend.
+    pub backend: &'static str,
+    /// Whether the `zeroize` feature is enabled.
+    pub zeroize: bool,
+    /// The value of `MAX_OUTPUT_LEN`.
+    pub max_output_len: usize,
+}
+
+/// Report the configuration this crate was built with, for diagnostics.
+pub fn info() -> CrateInfo {
+    CrateInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        backend: "scalar",
+        zeroize: cfg!(feature="zeroize"),
+        max_output_len: MAX_OUTPUT_LEN,
+    }
+}
@@ -0,0 +1,154 @@
+//! Access to the implementation, for testing and research. Nothing in this
+//! module is covered by semver guarantees, and it should not be enabled in
+//! production builds.
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
+use crypto_mac::{Mac, MacResult};
+use crypto_mac::generic_array::GenericArray;
+use crypto_mac::generic_array::typenum::{U32, U64};
+use sha2::{Digest, Sha512};
+#[cfg(feature="std")]
+use std::vec::Vec;
+
+use bhash::{pbkdf2_block, BHASH_OUTPUT_SIZE};
+use BhashKeySize;
+
+/// The bcrypt hash function: the PRF of bcrypt_pbkdf, keyed with the SHA-512
+/// hash of the passphrase and applied to the SHA-512 hash of its input.
+pub fn bhash(
+    sha2_pass: &GenericArray<u8, BhashKeySize>, sha2_salt: &GenericArray<u8, U64>,
+) -> [u8; BHASH_OUTPUT_SIZE] {
+    ::bhash::bhash(sha2_pass, sha2_salt)
+}
+
+/// `bhash` wrapped as a MAC keyed by the SHA-512 hash of the passphrase, so
+/// that it can be driven by the generic PBKDF2 implementation.
+///
+/// The key must be the 64-byte SHA-512 digest of the passphrase (see
+/// `BhashKeySize`), *not* the passphrase itself; `Mac::new_varkey` with a
+/// key of any other length fails. The MAC input is the salt, which is hashed
+/// with `D`, and the output is the 32-byte `bhash`.
+///
+/// `D` is the SHA-512 implementation used to hash the salt; it must be the
+/// same one used to hash the passphrase into the key.
+///
+/// The derivation itself does not go through this type, as driving it with a
+/// generic PBKDF2 implementation hashes the whole salt again for every block;
+/// it is provided to check the implementation against such a driver.
+///
+/// ```
+/// # extern crate bcrypt_pbkdf;
+/// # extern crate crypto_mac;
+/// # extern crate sha2;
+/// # fn main() {
+/// use bcrypt_pbkdf::internals::Bhash;
+/// use crypto_mac::Mac;
+/// use sha2::{Digest, Sha512};
+///
+/// let mut mac = <Bhash>::new(&Sha512::digest(b"passphrase"));
+/// mac.input(b"salt");
+/// assert_eq!(mac.result().code().len(), 32);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Bhash<D = Sha512> {
+    sha2_pass: GenericArray<u8, U64>,
+    salt: D,
+}
+
+impl<D> Mac for Bhash<D>
+    where D: Digest<OutputSize = U64> + Clone
+{
+    type OutputSize = U32;
+    type KeySize = U64;
+
+    fn new(key: &GenericArray<u8, Self::KeySize>) -> Self {
+        Bhash {
+            sha2_pass: *key,
+            salt: D::new(),
+        }
+    }
+
+    fn input(&mut self, data: &[u8]) {
+        Digest::input(&mut self.salt, data);
+    }
+
+    fn reset(&mut self) {
+        Digest::reset(&mut self.salt);
+    }
+
+    fn result(self) -> MacResult<Self::OutputSize> {
+        let output = ::bhash::bhash(&self.sha2_pass, &self.salt.result());
+        MacResult::new(GenericArray::clone_from_slice(&output))
+    }
+}
+
+/// Compute PBKDF2 block `block_index` (counting from zero; the PBKDF2 block
+/// counter is `block_index + 1`) of a derivation from the SHA-512 hash of
+/// the passphrase.
+///
+/// Block `j` supplies bytes `j`, `j + stride`, `j + 2 * stride`, ... of a
+/// `stride`-block bcrypt_pbkdf output (see `transpose_index`).
+///
+/// # Panics
+/// Panics if `block_index` is `u32::MAX`, as its counter does not fit in 32
+/// bits.
+pub fn derive_block(
+    sha2_pass: &GenericArray<u8, BhashKeySize>, salt: &[u8], rounds: u32,
+    block_index: u32,
+) -> [u8; BHASH_OUTPUT_SIZE] {
+    assert!(block_index < u32::MAX, "PBKDF2 block counter overflow");
+
+    let mut counter = [0u8; 4];
+    BigEndian::write_u32(&mut counter, block_index + 1);
+    let mut block = [0u8; BHASH_OUTPUT_SIZE];
+    pbkdf2_block(&Sha512::new().chain(salt), &counter, rounds, &mut block,
+        |sha2_salt| ::bhash::bhash(sha2_pass, sha2_salt));
+    block
+}
+
+/// Position in the PBKDF2 output (the concatenated 32-byte blocks) of byte
+/// `i` of a bcrypt_pbkdf output made of `stride` blocks.
+pub fn transpose_index(i: usize, stride: usize) -> usize {
+    ::transpose_index(i, stride)
+}
+
+/// Derive into `output` like `bcrypt_pbkdf`, and also return the PBKDF2 output
+/// it was taken from: the concatenated 32-byte blocks, `stride * 32` bytes
+/// long, where `stride` is the number of blocks needed for `output`.
+///
+/// Byte `i` of `output` is byte `transpose_index(i, stride)` of the returned
+/// grid.
+#[cfg(feature="std")]
+pub fn derive_with_grid(passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8]) -> Vec<u8> {
+    let stride = ::stride(output.len());
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+
+    let mut generated = vec![0u8; stride * BHASH_OUTPUT_SIZE];
+    ::generate::<Sha512>(&sha2_pass, salt, rounds, &mut generated);
+    ::wipe(&mut sha2_pass);
+    ::transpose(&generated, output);
+    generated
+}
+
+/// Derive into `output` like `bcrypt_pbkdf`, but with the PBKDF2 block
+/// counters appended to the salt in little-endian byte order, as done by
+/// some broken ports.
+///
+/// This is a debugging aid for differential testing against such ports: the
+/// output is unrelated to that of `bcrypt_pbkdf`, as even the first counter
+/// differs.
+pub fn derive_le_index(passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8]) {
+    ::derive_with_counter(passphrase, salt, rounds, output, LittleEndian::write_u32);
+}
+
+/// `bcrypt_pbkdf_redundant` with a fault injected into its second
+/// derivation, which must make it fail with `Err(Error::ComputationMismatch)`.
+///
+/// The fault only affects this call, so other derivations running at the
+/// same time are not disturbed.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_redundant_faulty(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) -> Result<(), ::Error> {
+    ::redundant::redundant(passphrase, salt, rounds, output, true)
+}
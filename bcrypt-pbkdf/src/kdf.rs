@@ -0,0 +1,40 @@
+#![cfg(feature="std")]
+use errors::Error;
+use super::derive;
+
+/// A password-based key derivation function, for applications which select
+/// the KDF at runtime (e.g. through a `Box<dyn Kdf>`).
+///
+/// The implementor carries the KDF's cost parameters; the passphrase and salt
+/// are given for each derivation, and `output` is filled entirely.
+pub trait Kdf {
+    /// Derive a key from `passphrase` and `salt` into `output`.
+    fn derive(&self, passphrase: &[u8], salt: &[u8], output: &mut [u8]) -> Result<(), Error>;
+}
+
+/// bcrypt_pbkdf as a `Kdf`, with the number of rounds to apply.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BcryptPbkdf {
+    /// The number of rounds.
+    pub rounds: u32,
+}
+
+impl BcryptPbkdf {
+    /// bcrypt_pbkdf with `rounds` rounds.
+    pub fn new(rounds: u32) -> BcryptPbkdf {
+        BcryptPbkdf { rounds }
+    }
+}
+
+impl Kdf for BcryptPbkdf {
+    /// The same as `bcrypt_pbkdf`, except that the passphrase does not have to
+    /// be valid UTF-8. This only fails, with `Err(Error::InvalidRounds)`, if
+    /// `rounds` is zero.
+    fn derive(&self, passphrase: &[u8], salt: &[u8], output: &mut [u8]) -> Result<(), Error> {
+        if self.rounds == 0 {
+            Err(Error::InvalidRounds)?;
+        }
+        derive(passphrase, salt, self.rounds, output);
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+#![cfg(feature="legacy")]
+use byteorder::{ByteOrder, BigEndian};
+use sha2::{Digest, Sha512};
+
+use bhash::bhash;
+#[cfg(feature="std")]
+use bhash::BHASH_OUTPUT_SIZE;
+use super::{derive_blockwise, derive_with_counter, wipe};
+#[cfg(feature="std")]
+use super::{generate, stride, transpose};
+
+/// A non-standard bcrypt_pbkdf which numbers the PBKDF2 blocks from zero
+/// rather than one: the big-endian block counter appended to the salt is
+/// overridden with the zero-based block index.
+///
+/// This is a compatibility shim for reading data produced by
+/// pre-standardization implementations which encoded the counter this way;
+/// it does **not** interoperate with OpenSSH or OpenBSD, and must not be used
+/// for anything new. The first counter is `00 00 00 00` instead of
+/// `00 00 00 01`, so block `j + 1` of this function is block `j` of
+/// `bcrypt_pbkdf`, and the output is otherwise unrelated to it. Its output is
+/// pinned by the `test_legacy_counter` test.
+pub fn bcrypt_pbkdf_legacy_counter(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) {
+    derive_with_counter(passphrase, salt, rounds, output,
+        |counter, block| BigEndian::write_u32(counter, block - 1));
+}
+
+/// The bcrypt_pbkdf function with zero rounds, for reproducing keys derived
+/// by legacy systems which (wrongly) used them.
+///
+/// Every other function rejects zero rounds, as OpenSSH and OpenBSD do.
+/// Before that check was added, zero rounds silently gave the same output as
+/// one round, as the first bcrypt hash of each block is always computed; this
+/// reproduces that (by deriving with one round), under a name which makes its
+/// use deliberate. It must not be used for anything new. Its output is pinned by
+/// the `test_legacy_zero_rounds` test.
+pub fn bcrypt_pbkdf_allow_zero_rounds(passphrase: &str, salt: &[u8], output: &mut [u8]) {
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    derive_blockwise(salt, 1, output, |sha2_salt| bhash(&sha2_pass, sha2_salt));
+    wipe(&mut sha2_pass);
+}
+
+/// The bcrypt_pbkdf function with its output written in reverse byte order,
+/// as stored by some legacy tools.
+///
+/// This is a compatibility shim for reading such files; it is **not** the
+/// standard output and must not be used for anything new. `output` holds the
+/// output of `bcrypt_pbkdf` for the same length, last byte first. Its output
+/// is pinned by the `test_legacy_reversed` test.
+pub fn bcrypt_pbkdf_reversed(passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8]) {
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    derive_blockwise(salt, rounds, output, |sha2_salt| bhash(&sha2_pass, sha2_salt));
+    wipe(&mut sha2_pass);
+    output.reverse();
+}
+
+/// Which derivation an output was found to come from, as reported by
+/// `looks_like_bcrypt_pbkdf`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Classification {
+    /// The output of `bcrypt_pbkdf`.
+    BcryptPbkdf,
+    /// The PBKDF2 output with the bcrypt hash, truncated to the candidate's
+    /// length without bcrypt_pbkdf's transpose, as produced by ports which
+    /// forgot it.
+    Untransposed,
+    /// Both: for outputs of at most 32 bytes (a single PBKDF2 block) the
+    /// transpose does not change anything, so the two can not be told apart.
+    Ambiguous,
+    /// Neither.
+    NoMatch,
+}
+
+/// Find out whether `candidate` was derived from `passphrase`, `salt` and
+/// `rounds` by `bcrypt_pbkdf`, or by a port which forgot the transpose of its
+/// output, e.g. to diagnose a key file written by a broken implementation.
+///
+/// The PBKDF2 output is computed once and compared against the candidate
+/// both after and without the transpose. The comparisons are not constant
+/// time; this is a debugging aid, not a way to check passphrases.
+#[cfg(feature="std")]
+pub fn looks_like_bcrypt_pbkdf(
+    passphrase: &str, salt: &[u8], rounds: u32, candidate: &[u8],
+) -> Classification {
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    let mut generated = vec![0u8; stride(candidate.len()) * BHASH_OUTPUT_SIZE];
+    generate::<Sha512>(&sha2_pass, salt, rounds, &mut generated);
+    wipe(&mut sha2_pass);
+    let mut transposed = vec![0u8; candidate.len()];
+    transpose(&generated, &mut transposed);
+
+    let standard = transposed[..] == *candidate;
+    let untransposed = generated[..candidate.len()] == *candidate;
+    wipe(&mut generated);
+    wipe(&mut transposed);
+
+    match (standard, untransposed) {
+        (true, true) => Classification::Ambiguous,
+        (true, false) => Classification::BcryptPbkdf,
+        (false, true) => Classification::Untransposed,
+        (false, false) => Classification::NoMatch,
+    }
+}
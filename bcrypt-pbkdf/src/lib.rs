@@ -0,0 +1,746 @@
+//! This crate implements the bcrypt_pbkdf key derivation function, a variant
+//! of PBKDF2 which uses a bcrypt-based hash function as its PRF. It is used by
+//! OpenSSH to derive the encryption key and IV for encrypted private keys.
+//!
+//! Functions which need to allocate are only available with the default `std`
+//! feature; `verify_with_scratch`, `verify_fixed_len`, `bcrypt_pbkdf_fixed`,
+//! `bcrypt_pbkdf_in_place`, `checked_bcrypt_pbkdf`, `bcrypt_pbkdf_openssh`,
+//! `bcrypt_pbkdf_cancellable` and `bcrypt_pbkdf_on_block` only use the stack
+//! or caller-provided memory and are also available in `no_std` builds.
+//!
+//! Like OpenBSD, every function rejects zero rounds: those returning a
+//! `Result` return `Err(Error::InvalidRounds)`, and the others panic.
+//!
+//! The `hkdf` feature adds `bcrypt_pbkdf_then_hkdf`, which expands a
+//! bcrypt_pbkdf output into more key material with HKDF, and the `hmac`
+//! feature adds `bcrypt_pbkdf_tagged`, which authenticates a derived key with
+//! HMAC-SHA256 for storage.
+//!
+//! The `heapless` feature adds `bcrypt_pbkdf_heapless`, which returns its
+//! output in a `heapless::Vec` and works without an allocator.
+//!
+//! The `rand_core` feature adds `Credential`, which stores a hash of a
+//! passphrase with a random salt for verifying it later.
+//!
+//! The `openssh` feature adds `parse_openssh_kdf`, which reads the KDF
+//! parameters from an OpenSSH private key, `derive_for_key_type`, which
+//! derives the key and IV of the cipher a key is encrypted with, and
+//! `derive_openssh_chacha`, which derives the keys of
+//! `chacha20-poly1305@openssh.com`. The `cipher` feature adds
+//! `openssh_passphrase_correct`, which checks a passphrase against a key, and
+//! `openssh_ed25519_seed`, which decrypts an ed25519 key (e.g. to convert it
+//! to an `age` identity).
+//!
+//! The `phc` feature adds `bcrypt_pbkdf_with_saltstring`, which takes the
+//! salt as a `password_hash::Salt`, and `verify_phc`, which checks a
+//! passphrase against a hash stored as a PHC string.
+//!
+//! The `serde` feature implements `Serialize` and `Deserialize` for
+//! `SaltedParams`.
+//!
+//! The `ssh-key` feature adds `derive_ssh_key_kdf`, which derives the cipher
+//! key and IV for decrypting an `ssh_key::PrivateKey`.
+//!
+//! The `legacy` feature adds non-standard variants of bcrypt_pbkdf, for
+//! compatibility with old or broken implementations only, and
+//! `looks_like_bcrypt_pbkdf`, which recognizes the output of ports which
+//! forgot bcrypt_pbkdf's transpose.
+//!
+//! The `nonstandard` feature adds variants of bcrypt_pbkdf which are
+//! deliberately incompatible with it, for building distinct KDFs, and
+//! `bhash_custom`, a bcrypt hash with adjustable internal rounds for
+//! research.
+//!
+//! The `salt-registry` feature adds `SaltRegistry` and
+//! `bcrypt_pbkdf_new_key`, which warn about a salt used for two keys, in
+//! builds with debug assertions enabled.
+//!
+//! The `capi` feature exports a C ABI for bcrypt_pbkdf, described in the
+//! `capi` module.
+//!
+//! The `internals` feature exposes hooks for testing in the `internals`
+//! module. It is not covered by semver guarantees.
+//!
+//! # References
+//! - [bcrypt_pbkdf](https://flak.tedunangst.com/post/bcrypt-pbkdf)
+//! - [OpenSSH private key format](https://github.com/openssh/openssh-portable/blob/master/PROTOCOL.key)
+#![no_std]
+#![doc(html_logo_url =
+    "https://raw.githubusercontent.com/RustCrypto/meta/master/logo_small.png")]
+#[cfg(feature="cipher")]
+extern crate aes;
+#[cfg(feature="openssh")]
+extern crate base64;
+extern crate blowfish;
+extern crate byteorder;
+extern crate crypto_mac;
+#[cfg(feature="heapless")]
+extern crate heapless;
+#[cfg(feature="hkdf")]
+extern crate hkdf;
+#[cfg(feature="hmac")]
+extern crate hmac;
+#[cfg(feature="phc")]
+extern crate password_hash;
+#[cfg(feature="rand_core")]
+extern crate rand_core;
+#[cfg(feature="serde")]
+extern crate serde;
+extern crate sha2;
+#[cfg(feature="ssh-key")]
+extern crate ssh_key;
+extern crate subtle;
+#[cfg(feature="zeroize")]
+extern crate zeroize;
+
+#[cfg(feature="std")]
+#[macro_use] extern crate std;
+
+mod bhash;
+mod budget;
+mod builder;
+mod cancel;
+#[cfg(feature="capi")]
+pub mod capi;
+mod check;
+mod checkpoint;
+mod checked;
+mod chunked;
+mod cost;
+mod credential;
+mod diagnostics;
+mod ed25519;
+mod emit;
+mod errors;
+mod expand;
+mod fixed;
+mod info;
+#[cfg(feature="internals")]
+pub mod internals;
+mod kdf;
+mod legacy;
+mod nonstandard;
+mod openssh;
+mod params;
+mod phc;
+mod pool;
+mod prepared;
+mod redundant;
+mod saltreg;
+mod saltsource;
+mod select;
+mod sshkey;
+mod tagged;
+mod verify;
+
+#[cfg(feature="std")]
+pub use budget::{bcrypt_pbkdf_budgeted, derive_within_budget, WorkBudget};
+#[cfg(feature="std")]
+pub use builder::BcryptPbkdfBuilder;
+pub use cancel::bcrypt_pbkdf_cancellable;
+#[cfg(feature="cipher")]
+pub use check::{openssh_checkint_bytes, openssh_passphrase_correct};
+pub use checked::{
+    bcrypt_pbkdf_openssh, checked_bcrypt_pbkdf, OPENSSH_MAX_ROUNDS, OPENSSH_MIN_ROUNDS,
+};
+#[cfg(feature="std")]
+pub use checkpoint::DerivationCheckpoint;
+#[cfg(feature="std")]
+pub use chunked::bcrypt_pbkdf_chunked;
+pub use cost::{equivalent_pbkdf2_iterations, estimate_ops, BHASH_BLOWFISH_ENCRYPTIONS};
+#[cfg(feature="std")]
+pub use cost::time_rounds;
+#[cfg(feature="rand_core")]
+pub use credential::{Credential, CREDENTIAL_LEN, CREDENTIAL_VERSION};
+#[cfg(feature="std")]
+pub use diagnostics::{derive_debug, DeriveDiagnostics};
+#[cfg(feature="cipher")]
+pub use ed25519::openssh_ed25519_seed;
+#[cfg(feature="std")]
+pub use emit::bcrypt_pbkdf_emit;
+pub use errors::Error;
+#[cfg(all(feature="hkdf", feature="std"))]
+pub use expand::bcrypt_pbkdf_then_hkdf;
+pub use fixed::{bcrypt_pbkdf_fixed, bcrypt_pbkdf_in_place};
+#[cfg(feature="heapless")]
+pub use fixed::bcrypt_pbkdf_heapless;
+pub use info::{info, CrateInfo, MAX_OUTPUT_LEN};
+#[cfg(feature="std")]
+pub use kdf::{BcryptPbkdf, Kdf};
+#[cfg(feature="legacy")]
+pub use legacy::{
+    bcrypt_pbkdf_allow_zero_rounds, bcrypt_pbkdf_legacy_counter, bcrypt_pbkdf_reversed,
+    Classification,
+};
+#[cfg(all(feature="legacy", feature="std"))]
+pub use legacy::looks_like_bcrypt_pbkdf;
+#[cfg(feature="nonstandard")]
+pub use nonstandard::{bcrypt_pbkdf_seeded, bhash_custom};
+#[cfg(feature="openssh")]
+pub use openssh::{
+    derive_for_key_type, derive_openssh_chacha, encode_kdfoptions,
+    parse_openssh_kdf, KdfOptions, ParsedKdf, SshCipher, SshKeyType,
+};
+pub use params::{Params, DEFAULT_MAX_PASSPHRASE_LEN, DEFAULT_MAX_SALT_LEN};
+#[cfg(feature="std")]
+pub use params::SaltedParams;
+#[cfg(all(feature="phc", feature="std"))]
+pub use phc::{bcrypt_pbkdf_with_saltstring, verify_phc};
+#[cfg(feature="std")]
+pub use pool::GridPool;
+#[cfg(feature="std")]
+pub use prepared::{
+    derive_lengths, derive_multi_salt, derive_shared, passphrase_prehash, FixedSaltCtx,
+    PreparedPassphrase,
+};
+#[cfg(feature="std")]
+pub use redundant::bcrypt_pbkdf_redundant;
+#[cfg(feature="salt-registry")]
+pub use saltreg::{bcrypt_pbkdf_new_key, SaltRegistry};
+#[cfg(feature="std")]
+pub use saltsource::{derive_with_salt_source, SaltSource};
+#[cfg(feature="std")]
+pub use select::bcrypt_pbkdf_select;
+#[cfg(all(feature="ssh-key", feature="std"))]
+pub use sshkey::derive_ssh_key_kdf;
+#[cfg(all(feature="hmac", feature="std"))]
+pub use tagged::bcrypt_pbkdf_tagged;
+#[cfg(feature="std")]
+pub use verify::{bcrypt_pbkdf_ct_eq, passphrases_equivalent, verify_from_reader};
+pub use verify::{verify_fixed_len, verify_with_scratch};
+
+use bhash::{bhash, pbkdf2_block, BHASH_OUTPUT_SIZE};
+use byteorder::{ByteOrder, BigEndian};
+use crypto_mac::generic_array::GenericArray;
+use crypto_mac::generic_array::typenum::U64;
+#[cfg(feature="std")]
+use crypto_mac::Mac;
+use sha2::{Digest, Sha512};
+#[cfg(feature="zeroize")]
+use zeroize::Zeroize;
+
+/// Size of the key of the bcrypt hash function: the 64-byte SHA-512 digest of
+/// the passphrase.
+///
+/// The passphrase is always hashed before being used as the bcrypt hash key;
+/// passing a raw passphrase where a key is expected is a misuse.
+///
+/// ```
+/// # extern crate bcrypt_pbkdf;
+/// # extern crate sha2;
+/// use bcrypt_pbkdf::BhashKeySize;
+/// use sha2::digest::generic_array::GenericArray;
+/// use sha2::{Digest, Sha512};
+///
+/// # fn main() {
+/// let key: GenericArray<u8, BhashKeySize> = Sha512::digest(b"passphrase");
+/// # let _ = key;
+/// # }
+/// ```
+pub type BhashKeySize = U64;
+
+/// The bcrypt_pbkdf function.
+///
+/// # Arguments
+/// - `passphrase` - The passphrase to process.
+/// - `salt` - The salt value to use as a byte vector.
+/// - `rounds` - The number of rounds to apply.
+/// - `output` - The resulting derived key is returned in this byte vector.
+///
+/// # Passphrase encoding
+/// The passphrase is hashed as the full UTF-8 byte sequence of `passphrase`.
+/// Interior NUL bytes are *not* treated as terminators: `"pass\0word"` and
+/// `"pass"` derive different keys. This differs from C implementations that
+/// take a NUL-terminated string (where the two would be identical), and is
+/// covered by the `test_embedded_nul_passphrase` test.
+///
+/// # Salt length
+/// The salt is hashed with SHA-512 together with the PBKDF2 block counter,
+/// so salts of any length are used in full. Unlike bcrypt, there is no
+/// 16-byte limit and nothing is truncated; this is covered by the
+/// `test_long_salt` test.
+///
+/// # Panics
+/// Panics if `rounds` is zero, which OpenBSD rejects too. Use
+/// `checked_bcrypt_pbkdf` to get an error instead.
+///
+/// # Examples
+/// The values are from the OpenBSD regression tests for bcrypt_pbkdf.
+///
+/// A 32-byte key, which takes a single PBKDF2 block:
+///
+/// ```
+/// use bcrypt_pbkdf::bcrypt_pbkdf;
+///
+/// let mut key = [0u8; 32];
+/// bcrypt_pbkdf("password", b"salt", 4, &mut key);
+/// assert_eq!(key, [
+///     0x5b, 0xbf, 0x0c, 0xc2, 0x93, 0x58, 0x7f, 0x1c,
+///     0x36, 0x35, 0x55, 0x5c, 0x27, 0x79, 0x65, 0x98,
+///     0xd4, 0x7e, 0x57, 0x90, 0x71, 0xbf, 0x42, 0x7e,
+///     0x9d, 0x8f, 0xbe, 0x84, 0x2a, 0xba, 0x34, 0xd9,
+/// ]);
+/// ```
+///
+/// A shorter key, with a salt containing a NUL byte:
+///
+/// ```
+/// use bcrypt_pbkdf::bcrypt_pbkdf;
+///
+/// let mut key = [0u8; 16];
+/// bcrypt_pbkdf("password", &[0], 4, &mut key);
+/// assert_eq!(key, [
+///     0xc1, 0x2b, 0x56, 0x62, 0x35, 0xee, 0xe0, 0x4c,
+///     0x21, 0x25, 0x98, 0x97, 0x0a, 0x57, 0x9a, 0x67,
+/// ]);
+/// ```
+///
+/// A 64-byte key, which interleaves two PBKDF2 blocks:
+///
+/// ```
+/// use bcrypt_pbkdf::bcrypt_pbkdf;
+///
+/// let mut key = vec![0u8; 64];
+/// bcrypt_pbkdf("password", b"salt", 8, &mut key);
+/// assert_eq!(key, [
+///     0xe1, 0x36, 0x7e, 0xc5, 0x15, 0x1a, 0x33, 0xfa,
+///     0xac, 0x4c, 0xc1, 0xc1, 0x44, 0xcd, 0x23, 0xfa,
+///     0x15, 0xd5, 0x54, 0x84, 0x93, 0xec, 0xc9, 0x9b,
+///     0x9b, 0x5d, 0x9c, 0x0d, 0x3b, 0x27, 0xbe, 0xc7,
+///     0x62, 0x27, 0xea, 0x66, 0x08, 0x8b, 0x84, 0x9b,
+///     0x20, 0xab, 0x7a, 0xa4, 0x78, 0x01, 0x02, 0x46,
+///     0xe7, 0x4b, 0xba, 0x51, 0x72, 0x3f, 0xef, 0xa9,
+///     0xf9, 0x47, 0x4d, 0x65, 0x08, 0x84, 0x5e, 0x8d,
+/// ]);
+/// ```
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf(passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8]) {
+    derive(passphrase.as_bytes(), salt, rounds, output);
+}
+
+/// The bcrypt_pbkdf function, for a passphrase made of the concatenation of
+/// `passphrase_parts`.
+///
+/// The parts are hashed in order without being copied into a single buffer;
+/// the result is the same as `bcrypt_pbkdf` with the concatenated passphrase.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_vectored(
+    passphrase_parts: &[&[u8]], salt: &[u8], rounds: u32, output: &mut [u8],
+) {
+    let mut hasher = Sha512::new();
+    for part in passphrase_parts {
+        hasher.input(part);
+    }
+    let mut sha2_pass = hasher.result();
+    derive_prehashed::<Sha512>(&sha2_pass, salt, rounds, output);
+    wipe(&mut sha2_pass);
+}
+
+/// The bcrypt_pbkdf function, returning the number of bytes written, which is
+/// always `output.len()`.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_write(passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8]) -> usize {
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    output.len()
+}
+
+/// The bcrypt_pbkdf function for an `n`-byte key, written into the first `n`
+/// bytes of `buffer`. The rest of `buffer` is left unchanged, and `n` is
+/// returned.
+///
+/// The key is the one `bcrypt_pbkdf` derives into an `n`-byte output, which
+/// is not in general a prefix of a longer output.
+///
+/// # Panics
+/// Panics if `n` is greater than `buffer.len()`.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_write_n(
+    passphrase: &str, salt: &[u8], rounds: u32, n: usize, buffer: &mut [u8],
+) -> usize {
+    bcrypt_pbkdf_write(passphrase, salt, rounds, &mut buffer[..n])
+}
+
+/// The bcrypt_pbkdf function, XORing the `buffer.len()`-byte derived key into
+/// `buffer` instead of overwriting it.
+///
+/// Each PBKDF2 byte is XORed directly into its position in `buffer`, so the
+/// key is never stored on its own. XOR being its own inverse, calling this
+/// twice with the same parameters restores the original contents.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_xor(passphrase: &str, salt: &[u8], rounds: u32, buffer: &mut [u8]) {
+    let stride = stride(buffer.len());
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    let mut generated = vec![0u8; stride * BHASH_OUTPUT_SIZE];
+    generate::<Sha512>(&sha2_pass, salt, rounds, &mut generated);
+    wipe(&mut sha2_pass);
+
+    for (i, byte) in buffer.iter_mut().enumerate() {
+        *byte ^= generated[transpose_index(i, stride)];
+    }
+    wipe(&mut generated);
+}
+
+/// The bcrypt_pbkdf function, deriving `key.len() + iv.len()` bytes and
+/// splitting them into `key` and `iv`, as OpenSSH does for a cipher key and
+/// IV.
+///
+/// This is a single derivation of the combined length: because of the
+/// transpose, deriving `key` and `iv` separately would give different bytes.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_key_iv(
+    passphrase: &str, salt: &[u8], rounds: u32, key: &mut [u8], iv: &mut [u8],
+) {
+    let stride = stride(key.len() + iv.len());
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    let mut generated = vec![0u8; stride * BHASH_OUTPUT_SIZE];
+    generate::<Sha512>(&sha2_pass, salt, rounds, &mut generated);
+    wipe(&mut sha2_pass);
+
+    for (i, byte) in key.iter_mut().chain(iv.iter_mut()).enumerate() {
+        *byte = generated[transpose_index(i, stride)];
+    }
+    wipe(&mut generated);
+}
+
+/// The bcrypt_pbkdf function, for a passphrase held in a `Box<[u8]>` which is
+/// consumed.
+///
+/// Taking ownership means no copy of the passphrase outlives the call: the box
+/// is zeroized before it is dropped, along with the intermediate buffers.
+#[cfg(all(feature="zeroize", feature="std"))]
+pub fn bcrypt_pbkdf_consume_boxed(
+    mut passphrase: std::boxed::Box<[u8]>, salt: &[u8], rounds: u32, output: &mut [u8],
+) {
+    derive(&passphrase, salt, rounds, output);
+    wipe(&mut passphrase);
+}
+
+/// The bcrypt_pbkdf function, for a passphrase which may be borrowed or
+/// owned.
+///
+/// The result is the same as `bcrypt_pbkdf`. An owned passphrase is consumed
+/// and, with the `zeroize` feature, zeroized before it is dropped.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_cow(
+    passphrase: std::borrow::Cow<str>, salt: &[u8], rounds: u32, output: &mut [u8],
+) {
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    if let std::borrow::Cow::Owned(passphrase) = passphrase {
+        wipe(&mut passphrase.into_bytes());
+    }
+}
+
+/// The bcrypt_pbkdf function, returning a `num_blocks * 32`-byte output as an
+/// iterator of its 32-byte chunks, in order.
+///
+/// Because of the transpose, every byte of the output depends on every PBKDF2
+/// block, so the whole output is derived before the first chunk is returned.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_blocks(
+    passphrase: &str, salt: &[u8], rounds: u32, num_blocks: usize,
+) -> impl Iterator<Item = [u8; BHASH_OUTPUT_SIZE]> {
+    let mut output = vec![0u8; num_blocks * BHASH_OUTPUT_SIZE];
+    derive(passphrase.as_bytes(), salt, rounds, &mut output);
+    let blocks: std::vec::Vec<[u8; BHASH_OUTPUT_SIZE]> = output
+        .chunks_exact(BHASH_OUTPUT_SIZE)
+        .map(|chunk| {
+            let mut block = [0u8; BHASH_OUTPUT_SIZE];
+            block.copy_from_slice(chunk);
+            block
+        })
+        .collect();
+    wipe(&mut output);
+    blocks.into_iter()
+}
+
+/// Compute the `num_blocks` PBKDF2 blocks of a `num_blocks * 32`-byte
+/// bcrypt_pbkdf output, calling `cb` with the (zero-based) index of each
+/// block as soon as it is done, e.g. to report progress.
+///
+/// The blocks are the ones *before* the transpose: byte `i` of the
+/// bcrypt_pbkdf output is byte `i / num_blocks` of block `i % num_blocks`.
+/// Each block is only lent to `cb`, and is overwritten by the next one. This
+/// does not allocate and is also available in `no_std` builds.
+///
+/// # Panics
+/// Panics if `num_blocks` is greater than `u32::MAX`, as the PBKDF2 block
+/// counter does not fit in 32 bits.
+pub fn bcrypt_pbkdf_on_block<F: FnMut(usize, &[u8; BHASH_OUTPUT_SIZE])>(
+    passphrase: &str, salt: &[u8], rounds: u32, num_blocks: usize, mut cb: F,
+) {
+    assert!(num_blocks as u64 <= u64::from(u32::MAX), "PBKDF2 block counter overflow");
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    let salted = Sha512::new().chain(salt);
+    let mut block = [0u8; BHASH_OUTPUT_SIZE];
+    let mut counter = [0u8; 4];
+    for j in 0..num_blocks {
+        BigEndian::write_u32(&mut counter, j as u32 + 1);
+        pbkdf2_block(&salted, &counter, rounds, &mut block,
+            |sha2_salt| bhash(&sha2_pass, sha2_salt));
+        cb(j, &block);
+    }
+    wipe(&mut block);
+    wipe(&mut sha2_pass);
+}
+
+/// The bcrypt_pbkdf function, followed by an in-place transform of the
+/// derived key, e.g. a final hash required by a protocol.
+///
+/// `post` is called once with `output` holding the final bcrypt_pbkdf output
+/// (after the transpose), and whatever it leaves there is the result.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_then<F: FnOnce(&mut [u8])>(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8], post: F,
+) {
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    post(output);
+}
+
+/// The bcrypt_pbkdf function, returning the number of 32-byte PBKDF2 blocks
+/// it generated, `ceil(output.len() / 32)`.
+///
+/// Byte `i` of `output` comes from block `i % stride`, so the returned stride
+/// also tells how the blocks are interleaved in the output, and the PBKDF2
+/// output takes `32 * stride` bytes of working memory.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_detailed(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) -> usize {
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    stride(output.len())
+}
+
+/// The bcrypt_pbkdf function, writing the derived key into 32-bit words.
+///
+/// `4 * output.len()` bytes are derived, and each group of 4 bytes is read as
+/// a **little-endian** word: `output[i]` is
+/// `u32::from_le_bytes(key[4 * i..4 * i + 4])`, where `key` is the output of
+/// `bcrypt_pbkdf` for the same length. This holds on any platform.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_u32(passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u32]) {
+    let mut bytes = vec![0u8; output.len() * 4];
+    derive(passphrase.as_bytes(), salt, rounds, &mut bytes);
+    byteorder::LittleEndian::read_u32_into(&bytes, output);
+    wipe(&mut bytes);
+}
+
+/// The bcrypt_pbkdf function for an output buffer which must be exactly `N`
+/// bytes long.
+///
+/// `bcrypt_pbkdf` fills whatever length it is given; this returns
+/// `Err(Error::LengthMismatch { .. })` without deriving anything if
+/// `output.len()` is not `N`, and `Err(Error::InvalidRounds)` if `rounds` is
+/// zero.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_exact<const N: usize>(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) -> Result<(), Error> {
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    if output.len() != N {
+        Err(Error::LengthMismatch { expected: N, got: output.len() })?;
+    }
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    Ok(())
+}
+
+/// The bcrypt_pbkdf function, using `D` as the SHA-512 implementation.
+///
+/// This allows plugging in an alternative (e.g. hardware accelerated)
+/// SHA-512. `D` is used both to hash the passphrase and, inside the bcrypt
+/// hash, to hash the salt; it must compute standard SHA-512 for the result to
+/// match `bcrypt_pbkdf`, which is `bcrypt_pbkdf_with_digest::<Sha512>`.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_with_digest<D>(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+)
+    where D: Digest<OutputSize = U64> + Clone + Sync
+{
+    let mut sha2_pass = D::digest(passphrase.as_bytes());
+    derive_prehashed::<D>(&sha2_pass, salt, rounds, output);
+    wipe(&mut sha2_pass);
+}
+
+/// The bcrypt_pbkdf function, deriving a key for the MAC `M`.
+///
+/// Exactly `M::KeySize` bytes are derived (e.g. 64 for HMAC-SHA256, whose key
+/// is a SHA-256 block), ready to be passed to `M::new`. The result is the same
+/// as `bcrypt_pbkdf` for that length.
+#[cfg(feature="std")]
+pub fn derive_mac_key<M: Mac>(
+    passphrase: &str, salt: &[u8], rounds: u32,
+) -> GenericArray<u8, M::KeySize> {
+    let mut key = GenericArray::default();
+    derive(passphrase.as_bytes(), salt, rounds, &mut key);
+    key
+}
+
+/// The bcrypt_pbkdf function, returning an `out_len`-byte key which is
+/// zeroized when dropped.
+///
+/// The intermediate buffers used by the derivation are zeroized as well.
+#[cfg(all(feature="zeroize", feature="std"))]
+pub fn bcrypt_pbkdf_zeroizing(
+    passphrase: &str, salt: &[u8], rounds: u32, out_len: usize,
+) -> zeroize::Zeroizing<std::vec::Vec<u8>> {
+    let mut output = zeroize::Zeroizing::new(vec![0u8; out_len]);
+    derive(passphrase.as_bytes(), salt, rounds, &mut output);
+    output
+}
+
+/// The bcrypt_pbkdf function, taking ownership of `passphrase` and zeroizing
+/// it (including any spare capacity) once the key has been derived.
+///
+/// The intermediate buffers used by the derivation are zeroized as well.
+#[cfg(all(feature="zeroize", feature="std"))]
+pub fn bcrypt_pbkdf_take_string(
+    passphrase: std::string::String, salt: &[u8], rounds: u32, output: &mut [u8],
+) {
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    passphrase.into_bytes().zeroize();
+}
+
+/// Number of 32-byte PBKDF2 blocks needed for an output of `len` bytes.
+fn stride(len: usize) -> usize {
+    len.div_ceil(BHASH_OUTPUT_SIZE)
+}
+
+/// Position in the PBKDF2 output of byte `i` of the bcrypt_pbkdf output.
+fn transpose_index(i: usize, stride: usize) -> usize {
+    let chunk_num = i % stride;
+    let chunk_index = i / stride;
+    chunk_num * BHASH_OUTPUT_SIZE + chunk_index
+}
+
+/// Fill `output` from the PBKDF2 output `generated` (`stride` 32-byte blocks,
+/// with `stride` computed from `output.len()`) through `transpose_index`.
+fn transpose(generated: &[u8], output: &mut [u8]) {
+    let stride = stride(output.len());
+    if stride == 1 {
+        // transpose_index(i, 1) == i: the output is a prefix of the only block.
+        output.copy_from_slice(&generated[..output.len()]);
+        return;
+    }
+    for (i, out_byte) in output.iter_mut().enumerate() {
+        *out_byte = generated[transpose_index(i, stride)];
+    }
+}
+
+/// Fill `generated` (a whole number of 32-byte blocks) with PBKDF2 output.
+///
+/// This is `pbkdf2::<Bhash<D>>`, except that the salt is hashed once for the
+/// whole derivation instead of once per block (see `pbkdf2_block`). Hashing a
+/// 1 MiB salt costs about half a `bhash` call, so this only shows for large
+/// salts and long outputs: with a 1 MiB salt, a one-round 1024-byte (32
+/// block) derivation went from about 225 ms to 150 ms. With short salts the
+/// difference is not measurable.
+fn generate<D>(
+    sha2_pass: &GenericArray<u8, U64>, salt: &[u8], rounds: u32,
+    generated: &mut [u8],
+)
+    where D: Digest<OutputSize = U64> + Clone + Sync
+{
+    generate_range(sha2_pass, &D::new().chain(salt), rounds, 0, generated);
+}
+
+/// Fill `generated` with the PBKDF2 output blocks starting at (zero-based)
+/// block `first_block`, where `salted` has already absorbed the salt.
+fn generate_range<D>(
+    sha2_pass: &GenericArray<u8, U64>, salted: &D, rounds: u32, first_block: usize,
+    generated: &mut [u8],
+)
+    where D: Digest<OutputSize = U64> + Clone
+{
+    let mut block = [0u8; BHASH_OUTPUT_SIZE];
+    let mut counter = [0u8; 4];
+    for (j, chunk) in generated.chunks_mut(BHASH_OUTPUT_SIZE).enumerate() {
+        BigEndian::write_u32(&mut counter, (first_block + j) as u32 + 1);
+        pbkdf2_block(salted, &counter, rounds, &mut block,
+            |sha2_salt| bhash(sha2_pass, sha2_salt));
+        chunk.copy_from_slice(&block);
+    }
+    wipe(&mut block);
+}
+
+/// Derive into `output` one PBKDF2 block at a time, without a grid buffer.
+/// `prf` computes each `bhash` from the SHA-512 hash of its input.
+fn derive_blockwise<F>(salt: &[u8], rounds: u32, output: &mut [u8], prf: F)
+    where F: FnMut(&[u8]) -> [u8; BHASH_OUTPUT_SIZE]
+{
+    derive_blockwise_with_counter(salt, rounds, output, BigEndian::write_u32, prf);
+}
+
+/// `derive_blockwise`, with `encode_counter` writing each (one-based) PBKDF2
+/// block counter into the 4 bytes appended to the salt.
+fn derive_blockwise_with_counter<F>(
+    salt: &[u8], rounds: u32, output: &mut [u8], encode_counter: fn(&mut [u8], u32),
+    mut prf: F,
+)
+    where F: FnMut(&[u8]) -> [u8; BHASH_OUTPUT_SIZE]
+{
+    let stride = stride(output.len());
+    let salted = Sha512::new().chain(salt);
+    let mut block = [0u8; BHASH_OUTPUT_SIZE];
+    let mut counter = [0u8; 4];
+    for j in 0..stride {
+        encode_counter(&mut counter, j as u32 + 1);
+        pbkdf2_block(&salted, &counter, rounds, &mut block, &mut prf);
+        // Block j supplies output bytes j, j + stride, j + 2 * stride, ...
+        for (out_byte, b) in output.iter_mut().skip(j).step_by(stride).zip(block.iter()) {
+            *out_byte = *b;
+        }
+    }
+    wipe(&mut block);
+}
+
+/// Derive into `output` from `passphrase` one PBKDF2 block at a time, with
+/// `encode_counter` as in `derive_blockwise_with_counter`. This is the single
+/// implementation of the non-standard block counters.
+#[cfg(any(feature="legacy", feature="internals"))]
+fn derive_with_counter(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+    encode_counter: fn(&mut [u8], u32),
+) {
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    derive_blockwise_with_counter(salt, rounds, output, encode_counter,
+        |sha2_salt| bhash(&sha2_pass, sha2_salt));
+    wipe(&mut sha2_pass);
+}
+
+/// Derive into `output`. The intermediate buffers are zeroized with the
+/// `zeroize` feature.
+#[cfg(feature="std")]
+fn derive(passphrase: &[u8], salt: &[u8], rounds: u32, output: &mut [u8]) {
+    let mut sha2_pass = Sha512::digest(passphrase);
+    derive_prehashed::<Sha512>(&sha2_pass, salt, rounds, output);
+    wipe(&mut sha2_pass);
+}
+
+/// Derive into `output` from the SHA-512 hash of the passphrase, zeroizing
+/// the pre-transpose grid with the `zeroize` feature.
+#[cfg(feature="std")]
+fn derive_prehashed<D>(
+    sha2_pass: &GenericArray<u8, U64>, salt: &[u8], rounds: u32,
+    output: &mut [u8],
+)
+    where D: Digest<OutputSize = U64> + Clone + Sync
+{
+    let stride = stride(output.len());
+
+    let mut generated = vec![0u8; stride * BHASH_OUTPUT_SIZE];
+    generate::<D>(sha2_pass, salt, rounds, &mut generated);
+
+    // Apply the bcrypt_pbkdf non-linear transformation
+    transpose(&generated, output);
+
+    wipe(&mut generated);
+}
+
+#[cfg(feature="zeroize")]
+fn wipe(buf: &mut [u8]) {
+    buf.zeroize();
+}
+
+#[cfg(not(feature="zeroize"))]
+fn wipe(_buf: &mut [u8]) {}
@@ -0,0 +1,47 @@
+#![cfg(feature="nonstandard")]
+use blowfish::Blowfish;
+use crypto_mac::generic_array::GenericArray;
+use crypto_mac::generic_array::typenum::U64;
+use sha2::{Digest, Sha512};
+
+use bhash::{
+    bhash_with_rounds, bhash_with_state, seed_words, BHASH_OUTPUT_SIZE, BHASH_SEED_WORDS,
+};
+use super::{derive_blockwise, wipe};
+use BhashKeySize;
+
+/// A non-standard bcrypt_pbkdf which encrypts `seed` in the bcrypt hash
+/// instead of the standard `"OxychromaticBlowfishSwatDynamite"`.
+///
+/// Using a seed specific to a protocol gives a KDF which is domain-separated
+/// from bcrypt_pbkdf. The output is **not** compatible with OpenSSH or
+/// OpenBSD, except when `seed` is the standard seed, in which case it is the
+/// same as `bcrypt_pbkdf`.
+pub fn bcrypt_pbkdf_seeded(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+    seed: &[u8; BHASH_OUTPUT_SIZE],
+) {
+    let seed = seed_words(seed);
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    derive_blockwise(salt, rounds, output, |sha2_salt| {
+        bhash_with_state(&mut Blowfish::bc_init_state(), &seed, &sha2_pass, sha2_salt)
+    });
+    wipe(&mut sha2_pass);
+}
+
+/// A non-standard bcrypt hash with `key_rounds` iterations of its key
+/// expansion (alternately with the salt and the passphrase hash) and
+/// `enc_rounds` encryptions of the seed, instead of 64 of each, for
+/// cryptanalysis and differential testing of modified variants.
+///
+/// The arguments are those of the standard bcrypt hash (see
+/// `internals::bhash`), which is `bhash_custom(sha2_pass, sha2_salt, 64, 64)`.
+/// With any other counts the output is **not** compatible with bcrypt_pbkdf
+/// and must not be used to protect anything.
+pub fn bhash_custom(
+    sha2_pass: &GenericArray<u8, BhashKeySize>, sha2_salt: &GenericArray<u8, U64>,
+    key_rounds: usize, enc_rounds: usize,
+) -> [u8; BHASH_OUTPUT_SIZE] {
+    bhash_with_rounds(&mut Blowfish::bc_init_state(), &BHASH_SEED_WORDS, sha2_pass, sha2_salt,
+        key_rounds, enc_rounds)
+}
@@ -0,0 +1,216 @@
+#[cfg(all(feature="serde", feature="std"))]
+use core::convert::TryFrom;
+#[cfg(feature="std")]
+use std::vec::Vec;
+
+#[cfg(all(feature="serde", feature="std"))]
+use serde::{Deserialize, Serialize};
+
+use errors::Error;
+#[cfg(feature="std")]
+use info::MAX_OUTPUT_LEN;
+#[cfg(feature="std")]
+use super::derive;
+
+/// Default ceiling on the salt length accepted by `Params`: 1 MiB.
+pub const DEFAULT_MAX_SALT_LEN: usize = 1 << 20;
+
+/// Default ceiling on the passphrase length accepted by `Params`: 1 MiB.
+pub const DEFAULT_MAX_PASSPHRASE_LEN: usize = 1 << 20;
+
+/// bcrypt_pbkdf parameters, with validation of the inputs they are used with.
+///
+/// `bcrypt_pbkdf` accepts any input; `Params::derive` rejects inputs that
+/// are almost certainly mistakes before spending time on them.
+///
+/// `Params` implements `Eq` and `Hash` over all of its fields (the rounds,
+/// the output length and the validation settings), none of which is secret,
+/// so it can be used as a cache key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Params {
+    rounds: u32,
+    output_len: usize,
+    max_salt_len: usize,
+    max_passphrase_len: usize,
+    reject_zero_salt: bool,
+    reject_empty_passphrase: bool,
+}
+
+impl Params {
+    /// Create parameters deriving `output_len` bytes with `rounds` rounds.
+    pub fn new(rounds: u32, output_len: usize) -> Params {
+        Params {
+            rounds,
+            output_len,
+            max_salt_len: DEFAULT_MAX_SALT_LEN,
+            max_passphrase_len: DEFAULT_MAX_PASSPHRASE_LEN,
+            reject_zero_salt: false,
+            reject_empty_passphrase: false,
+        }
+    }
+
+    /// Set the longest salt accepted, in bytes. Defaults to
+    /// `DEFAULT_MAX_SALT_LEN`.
+    ///
+    /// The whole salt is hashed, so an overly long salt (most likely passed
+    /// by mistake) makes every derivation needlessly slow.
+    pub fn max_salt_len(mut self, max_salt_len: usize) -> Params {
+        self.max_salt_len = max_salt_len;
+        self
+    }
+
+    /// Set the longest passphrase accepted, in bytes. Defaults to
+    /// `DEFAULT_MAX_PASSPHRASE_LEN`.
+    ///
+    /// Passphrases of any length can be hashed, but one of megabytes is most
+    /// likely a bug, such as passing the wrong buffer.
+    pub fn max_passphrase_len(mut self, max_passphrase_len: usize) -> Params {
+        self.max_passphrase_len = max_passphrase_len;
+        self
+    }
+
+    /// Set whether a non-empty salt made only of zero bytes is rejected.
+    /// Defaults to `false`.
+    ///
+    /// Such a salt is valid, but usually means the salt was never filled in.
+    pub fn reject_zero_salt(mut self, reject: bool) -> Params {
+        self.reject_zero_salt = reject;
+        self
+    }
+
+    /// Set whether an empty passphrase is rejected. Defaults to `false`.
+    pub fn reject_empty_passphrase(mut self, reject: bool) -> Params {
+        self.reject_empty_passphrase = reject;
+        self
+    }
+
+    /// The number of rounds.
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+
+    /// The length of the derived key, in bytes.
+    pub fn output_len(&self) -> usize {
+        self.output_len
+    }
+
+    /// Check that `salt` is acceptable for these parameters.
+    pub fn check_salt(&self, salt: &[u8]) -> Result<(), Error> {
+        if salt.len() > self.max_salt_len {
+            Err(Error::SaltTooLong)?;
+        }
+        if self.reject_zero_salt && !salt.is_empty() && salt.iter().all(|&b| b == 0) {
+            Err(Error::AllZeroSalt)?;
+        }
+        Ok(())
+    }
+
+    /// Check that `passphrase` is acceptable for these parameters.
+    pub fn check_passphrase(&self, passphrase: &str) -> Result<(), Error> {
+        if passphrase.len() > self.max_passphrase_len {
+            Err(Error::PassphraseTooLong)?;
+        }
+        if self.reject_empty_passphrase && passphrase.is_empty() {
+            Err(Error::EmptyPassphrase)?;
+        }
+        Ok(())
+    }
+
+    /// Validate the inputs and derive a key from them. Returns
+    /// `Err(Error::InvalidRounds)` if the number of rounds is zero.
+    #[cfg(feature="std")]
+    pub fn derive(&self, passphrase: &str, salt: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.rounds == 0 {
+            Err(Error::InvalidRounds)?;
+        }
+        self.check_passphrase(passphrase)?;
+        self.check_salt(salt)?;
+
+        let mut output = vec![0u8; self.output_len];
+        derive(passphrase.as_bytes(), salt, self.rounds, &mut output);
+        Ok(output)
+    }
+}
+
+/// bcrypt_pbkdf parameters bundled with the salt they are used with, e.g. as
+/// stored alongside a credential, so that the passphrase is the only input
+/// left to `derive`.
+///
+/// The parameters are validated once, by `new`: the salt must be at most
+/// `DEFAULT_MAX_SALT_LEN` bytes, `rounds` must not be zero and `output_len`
+/// must be between 1 and `MAX_OUTPUT_LEN`.
+///
+/// With the `serde` feature, `SaltedParams` can be serialized as a struct with
+/// `salt`, `rounds` and `output_len` fields. Deserialization goes through
+/// `new`, so invalid parameters are rejected.
+#[cfg(feature="std")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature="serde", serde(try_from = "SaltedParamsFields"))]
+pub struct SaltedParams {
+    salt: Vec<u8>,
+    rounds: u32,
+    output_len: usize,
+}
+
+#[cfg(feature="std")]
+impl SaltedParams {
+    /// Validate and bundle the parameters.
+    ///
+    /// Returns `Err(Error::SaltTooLong)`, `Err(Error::InvalidRounds)` or
+    /// `Err(Error::InvalidOutputLen)` if the salt, rounds or output length is
+    /// invalid.
+    pub fn new(salt: &[u8], rounds: u32, output_len: usize) -> Result<SaltedParams, Error> {
+        if salt.len() > DEFAULT_MAX_SALT_LEN {
+            Err(Error::SaltTooLong)?;
+        }
+        if rounds == 0 {
+            Err(Error::InvalidRounds)?;
+        }
+        if output_len == 0 || output_len > MAX_OUTPUT_LEN {
+            Err(Error::InvalidOutputLen)?;
+        }
+        Ok(SaltedParams { salt: salt.to_vec(), rounds, output_len })
+    }
+
+    /// The salt.
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    /// The number of rounds.
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+
+    /// The length of the derived key, in bytes.
+    pub fn output_len(&self) -> usize {
+        self.output_len
+    }
+
+    /// Derive a key from `passphrase`; identical to calling `bcrypt_pbkdf`
+    /// with the bundled salt, rounds and output length.
+    pub fn derive(&self, passphrase: &str) -> Vec<u8> {
+        let mut output = vec![0u8; self.output_len];
+        derive(passphrase.as_bytes(), &self.salt, self.rounds, &mut output);
+        output
+    }
+}
+
+/// The fields of a serialized `SaltedParams`, before validation.
+#[cfg(all(feature="serde", feature="std"))]
+#[derive(Deserialize)]
+struct SaltedParamsFields {
+    salt: Vec<u8>,
+    rounds: u32,
+    output_len: usize,
+}
+
+#[cfg(all(feature="serde", feature="std"))]
+impl TryFrom<SaltedParamsFields> for SaltedParams {
+    type Error = Error;
+
+    fn try_from(fields: SaltedParamsFields) -> Result<SaltedParams, Error> {
+        SaltedParams::new(&fields.salt, fields.rounds, fields.output_len)
+    }
+}
@@ -0,0 +1,62 @@
+#![cfg(all(feature="phc", feature="std"))]
+use password_hash::{PasswordHash, Salt};
+use subtle::ConstantTimeEq;
+
+use errors::Error;
+use super::{derive, wipe};
+
+/// The PHC string format algorithm identifier used by `verify_phc`.
+const PHC_ALGORITHM: &str = "bcrypt-pbkdf";
+
+/// The bcrypt_pbkdf function, taking the salt as a `password_hash::Salt`
+/// (or `SaltString`, via `SaltString::as_salt`).
+///
+/// The salt is the "B64" (unpadded standard Base64) encoding used by the PHC
+/// string format, and is decoded to bytes before use; the derivation is the
+/// same as `bcrypt_pbkdf` with the decoded bytes. OpenSSH stores raw salt
+/// bytes, so this is only useful to share salts with other password hashers.
+/// Returns `Err(Error::InvalidFormat)` if the salt can not be decoded, and
+/// `Err(Error::InvalidRounds)` if `rounds` is zero.
+pub fn bcrypt_pbkdf_with_saltstring(
+    passphrase: &str, salt: Salt, rounds: u32, output: &mut [u8],
+) -> Result<(), Error> {
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    let mut buf = [0u8; Salt::MAX_LENGTH];
+    let salt = salt.decode_b64(&mut buf).map_err(|_| Error::InvalidFormat)?;
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    Ok(())
+}
+
+/// Check `passphrase` against a hash stored as a PHC string:
+/// `$bcrypt-pbkdf$r=<rounds>$<salt>$<hash>`, where the salt and hash are
+/// "B64" encoded (see `bcrypt_pbkdf_with_saltstring`).
+///
+/// The key is derived with the length of the stored hash and compared with it
+/// in constant time. Returns `Err(Error::InvalidFormat)` if `phc` can not be
+/// parsed, uses another algorithm, has a version, has parameters other than
+/// `r`, or lacks the rounds, salt or hash, and `Err(Error::InvalidRounds)` if
+/// the rounds are zero.
+pub fn verify_phc(passphrase: &str, phc: &str) -> Result<bool, Error> {
+    let hash = PasswordHash::new(phc).map_err(|_| Error::InvalidFormat)?;
+    if hash.algorithm.as_str() != PHC_ALGORITHM || hash.version.is_some() ||
+        hash.params.iter().any(|(name, _)| name.as_str() != "r")
+    {
+        Err(Error::InvalidFormat)?;
+    }
+    let rounds = hash.params.get_decimal("r").ok_or(Error::InvalidFormat)?;
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    let salt = hash.salt.ok_or(Error::InvalidFormat)?;
+    let expected = hash.hash.ok_or(Error::InvalidFormat)?;
+
+    let mut buf = [0u8; Salt::MAX_LENGTH];
+    let salt = salt.decode_b64(&mut buf).map_err(|_| Error::InvalidFormat)?;
+    let mut derived = vec![0u8; expected.len()];
+    derive(passphrase.as_bytes(), salt, rounds, &mut derived);
+    let equal = derived.ct_eq(expected.as_bytes());
+    wipe(&mut derived);
+    Ok(equal.into())
+}
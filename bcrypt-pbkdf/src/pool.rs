@@ -0,0 +1,49 @@
+#![cfg(feature="std")]
+use std::sync::Mutex;
+use std::vec::Vec;
+
+use sha2::{Digest, Sha512};
+
+use bhash::BHASH_OUTPUT_SIZE;
+use super::{generate, stride, transpose, wipe};
+
+/// A pool of grid buffers (the PBKDF2 output which bcrypt_pbkdf transposes
+/// into the key) which can be shared between threads deriving many keys.
+///
+/// Each derivation takes one buffer from the pool (allocating it if the pool
+/// is empty), grows it if needed, and returns it to the pool when done, so the
+/// number of allocations is bounded by the number of concurrent derivations
+/// rather than the number of requests. With the `zeroize` feature, buffers
+/// are zeroized before being returned. The pool never shrinks.
+#[derive(Default)]
+pub struct GridPool {
+    grids: Mutex<Vec<Vec<u8>>>,
+}
+
+impl GridPool {
+    /// Create an empty pool.
+    pub fn new() -> GridPool {
+        GridPool::default()
+    }
+
+    /// Derive a key into `output` using a pooled grid buffer; identical to
+    /// calling `bcrypt_pbkdf`.
+    pub fn derive(&self, passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8]) {
+        let mut grid = self.grids.lock().unwrap_or_else(|e| e.into_inner()).pop()
+            .unwrap_or_default();
+        grid.resize(stride(output.len()) * BHASH_OUTPUT_SIZE, 0);
+
+        let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+        generate::<Sha512>(&sha2_pass, salt, rounds, &mut grid);
+        wipe(&mut sha2_pass);
+        transpose(&grid, output);
+
+        wipe(&mut grid);
+        self.grids.lock().unwrap_or_else(|e| e.into_inner()).push(grid);
+    }
+
+    /// Number of idle buffers currently held by the pool.
+    pub fn idle(&self) -> usize {
+        self.grids.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
@@ -0,0 +1,129 @@
+#![cfg(feature="std")]
+use std::vec::Vec;
+
+use crypto_mac::generic_array::GenericArray;
+use crypto_mac::generic_array::typenum::U64;
+use sha2::{Digest, Sha512};
+
+use bhash::{bhash, BHASH_OUTPUT_SIZE};
+use super::{derive_blockwise, derive_prehashed, generate_range, stride, transpose, wipe};
+
+/// A passphrase which has been hashed with SHA-512 ahead of time, so that
+/// several derivations can be made from it without hashing it again.
+///
+/// `PreparedPassphrase` is `Send` and `Sync`: it is immutable after
+/// construction and `derive` only reads it, so one instance (e.g. in an
+/// `Arc`) can be used by several threads at once.
+#[derive(Clone)]
+pub struct PreparedPassphrase {
+    sha2_pass: GenericArray<u8, U64>,
+}
+
+impl PreparedPassphrase {
+    /// Hash `passphrase` for use in later derivations.
+    pub fn new(passphrase: &str) -> PreparedPassphrase {
+        PreparedPassphrase { sha2_pass: Sha512::digest(passphrase.as_bytes()) }
+    }
+
+    /// Derive a key into `output`; identical to calling `bcrypt_pbkdf` with
+    /// the original passphrase.
+    pub fn derive(&self, salt: &[u8], rounds: u32, output: &mut [u8]) {
+        derive_prehashed::<Sha512>(&self.sha2_pass, salt, rounds, output);
+    }
+}
+
+impl Drop for PreparedPassphrase {
+    fn drop(&mut self) {
+        wipe(&mut self.sha2_pass);
+    }
+}
+
+/// A salt and number of rounds fixed ahead of time, for deriving keys from
+/// many candidate passphrases (e.g. when auditing them against one stored
+/// key). This is the dual of `PreparedPassphrase`.
+///
+/// The SHA-512 state after absorbing the salt is computed once and shared by
+/// every derivation, which only feeds it the 4-byte PBKDF2 block counters.
+#[derive(Clone)]
+pub struct FixedSaltCtx {
+    salted: Sha512,
+    rounds: u32,
+}
+
+impl FixedSaltCtx {
+    /// Precompute the salt-dependent state for `salt` and `rounds`.
+    pub fn new(salt: &[u8], rounds: u32) -> FixedSaltCtx {
+        FixedSaltCtx { salted: Sha512::new().chain(salt), rounds }
+    }
+
+    /// Derive a key into `output`; identical to calling `bcrypt_pbkdf` with
+    /// `passphrase` and the fixed salt and rounds.
+    pub fn derive_candidate(&self, passphrase: &str, output: &mut [u8]) {
+        let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+        let mut generated = vec![0u8; stride(output.len()) * BHASH_OUTPUT_SIZE];
+        generate_range(&sha2_pass, &self.salted, self.rounds, 0, &mut generated);
+        transpose(&generated, output);
+        wipe(&mut sha2_pass);
+        wipe(&mut generated);
+    }
+}
+
+/// The SHA-512 hash of `passphrase`, which is exactly what bcrypt_pbkdf uses
+/// as the key of its bcrypt hash, for passing to `derive_shared`.
+///
+/// This lets callers cache the hash themselves, e.g. keyed by a user ID.
+/// It is as sensitive as the passphrase: anyone who has it can derive every
+/// key the passphrase derives.
+pub fn passphrase_prehash(passphrase: &[u8]) -> [u8; 64] {
+    let mut sha2_pass = Sha512::digest(passphrase);
+    let mut prehash = [0u8; 64];
+    prehash.copy_from_slice(&sha2_pass);
+    wipe(&mut sha2_pass);
+    prehash
+}
+
+/// Derive a key into `output` from `prehash`, the SHA-512 hash of the
+/// passphrase (see `passphrase_prehash`); identical to calling `bcrypt_pbkdf` with the passphrase.
+///
+/// This is the lowest-level way to reuse the passphrase hash: it can be
+/// computed once and shared between threads, e.g. as an `Arc<[u8; 64]>`.
+/// Treat `prehash` like the passphrase itself, and wipe it when done.
+pub fn derive_shared(prehash: &[u8; 64], salt: &[u8], rounds: u32, output: &mut [u8]) {
+    derive_prehashed::<Sha512>(GenericArray::from_slice(prehash), salt, rounds, output);
+}
+
+/// Derive one key for each of `lengths` from the same passphrase, salt and
+/// rounds.
+///
+/// The passphrase hash is shared, but each key still requires a full
+/// derivation: bcrypt_pbkdf interleaves its 32-byte PBKDF2 blocks across the
+/// whole output, so keys whose lengths need a different number of blocks
+/// (e.g. 32 and 33 bytes) are *not* prefixes of one another.
+pub fn derive_lengths(
+    prepared: &PreparedPassphrase, salt: &[u8], rounds: u32, lengths: &[usize],
+) -> Vec<Vec<u8>> {
+    lengths.iter().map(|&len| {
+        let mut output = vec![0u8; len];
+        prepared.derive(salt, rounds, &mut output);
+        output
+    }).collect()
+}
+
+/// Derive one `out_len`-byte key for each of `salts` from the same
+/// passphrase, e.g. to check a candidate passphrase against many stored
+/// credentials.
+///
+/// Only the passphrase hash is shared by the derivations. Nothing of the
+/// Blowfish state can be: its initial state is a copy of constants, as cheap
+/// to make as to reset, and everything after it depends on the salt. Each key
+/// is identical to the output of `bcrypt_pbkdf` with the same salt.
+pub fn derive_multi_salt(
+    prepared: &PreparedPassphrase, salts: &[&[u8]], rounds: u32, out_len: usize,
+) -> Vec<Vec<u8>> {
+    salts.iter().map(|salt| {
+        let mut output = vec![0u8; out_len];
+        derive_blockwise(salt, rounds, &mut output,
+            |sha2_salt| bhash(&prepared.sha2_pass, sha2_salt));
+        output
+    }).collect()
+}
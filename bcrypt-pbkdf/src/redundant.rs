@@ -0,0 +1,45 @@
+#![cfg(feature="std")]
+use subtle::ConstantTimeEq;
+
+use errors::Error;
+use super::{derive, wipe};
+
+/// The bcrypt_pbkdf function, computed twice as a check against faults.
+///
+/// The key is derived into `output` and again into a separate buffer, and the
+/// two are compared in constant time. If they differ (which indicates a fault
+/// during one of the computations, such as a bit flip), `output` is zeroed
+/// and `Err(Error::ComputationMismatch)` is returned. This takes twice as long
+/// as `bcrypt_pbkdf`. Returns `Err(Error::InvalidRounds)` if `rounds` is zero.
+pub fn bcrypt_pbkdf_redundant(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) -> Result<(), Error> {
+    redundant(passphrase, salt, rounds, output, false)
+}
+
+/// `bcrypt_pbkdf_redundant`, corrupting the second derivation if `fault` is
+/// set, to simulate a fault during the computation.
+pub(crate) fn redundant(
+    passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8], fault: bool,
+) -> Result<(), Error> {
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    derive(passphrase.as_bytes(), salt, rounds, output);
+
+    let mut check = vec![0u8; output.len()];
+    derive(passphrase.as_bytes(), salt, rounds, &mut check);
+    if fault {
+        if let Some(b) = check.first_mut() {
+            *b ^= 1;
+        }
+    }
+
+    let equal = bool::from(output.ct_eq(&check));
+    wipe(&mut check);
+    if !equal {
+        output.iter_mut().for_each(|b| *b = 0);
+        Err(Error::ComputationMismatch)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+#![cfg(feature="salt-registry")]
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use super::derive;
+
+/// The salts used so far by `bcrypt_pbkdf_new_key`, for catching a salt
+/// used for two different keys, e.g. because of a broken RNG or a salt
+/// copied from another key.
+///
+/// Only the SHA-256 hash of each salt is kept. It can be shared between
+/// threads. This is a development aid: in builds without debug assertions it
+/// records nothing, warns about nothing, and every salt is reported as new.
+#[derive(Debug, Default)]
+pub struct SaltRegistry {
+    seen: Mutex<HashSet<[u8; 32]>>,
+}
+
+impl SaltRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        SaltRegistry::default()
+    }
+
+    /// Record `salt`, returning `false` if it had already been recorded.
+    ///
+    /// A reused salt is also reported with a warning on standard error (which
+    /// does not include the salt). Without debug assertions this does nothing
+    /// and returns `true`.
+    pub fn record(&self, salt: &[u8]) -> bool {
+        if !cfg!(debug_assertions) {
+            return true;
+        }
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&Sha256::digest(salt));
+        let fresh = self.seen.lock().unwrap_or_else(|e| e.into_inner()).insert(hash);
+        if !fresh {
+            eprintln!("warning: bcrypt_pbkdf: a salt was used for more than one key");
+        }
+        fresh
+    }
+
+    /// The number of distinct salts recorded.
+    pub fn len(&self) -> usize {
+        self.seen.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Whether no salts have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The bcrypt_pbkdf function for creating a new key, recording `salt` in
+/// `registry`.
+///
+/// Returns `false`, and warns on standard error, if `salt` was already used
+/// with `registry` (which is never detected without debug assertions, see
+/// `SaltRegistry::record`); the key is derived either way.
+pub fn bcrypt_pbkdf_new_key(
+    registry: &SaltRegistry, passphrase: &str, salt: &[u8], rounds: u32, output: &mut [u8],
+) -> bool {
+    let fresh = registry.record(salt);
+    derive(passphrase.as_bytes(), salt, rounds, output);
+    fresh
+}
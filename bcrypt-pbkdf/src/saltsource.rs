@@ -0,0 +1,30 @@
+#![cfg(feature="std")]
+use std::vec::Vec;
+
+use errors::Error;
+use super::derive;
+
+/// Where the salt of a derivation comes from, for deployments which keep
+/// salts outside the application, e.g. in a KMS or HSM.
+pub trait SaltSource {
+    /// Fetch the salt. Sources which fail for a reason of their own (e.g.
+    /// the KMS is unreachable) should return `Err(Error::SaltUnavailable)`.
+    fn salt(&self) -> Result<Vec<u8>, Error>;
+}
+
+/// The bcrypt_pbkdf function, with the salt fetched from `source`.
+///
+/// The salt is fetched once, before deriving anything. If `source` fails,
+/// its error is returned and `output` is left unchanged; otherwise the result
+/// is the same as `bcrypt_pbkdf` with that salt. Returns
+/// `Err(Error::InvalidRounds)` without fetching the salt if `rounds` is zero.
+pub fn derive_with_salt_source<S: SaltSource + ?Sized>(
+    passphrase: &str, source: &S, rounds: u32, output: &mut [u8],
+) -> Result<(), Error> {
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    let salt = source.salt()?;
+    derive(passphrase.as_bytes(), &salt, rounds, output);
+    Ok(())
+}
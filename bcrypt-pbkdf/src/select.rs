@@ -0,0 +1,27 @@
+#![cfg(feature="std")]
+use subtle::{Choice, ConditionallySelectable};
+
+use super::{derive, wipe};
+
+/// Derive the key for `real_pass` if `use_real` is set, and the key for
+/// `decoy_pass` otherwise, without branching on `use_real`.
+///
+/// Both keys are always derived, in the same order, so this takes twice as
+/// long as `bcrypt_pbkdf`; the bytes of `output` are then selected in
+/// constant time. The two passphrases should have the same length, as the
+/// time taken to hash them depends on it.
+pub fn bcrypt_pbkdf_select(
+    real_pass: &str, decoy_pass: &str, use_real: Choice, salt: &[u8], rounds: u32,
+    output: &mut [u8],
+) {
+    let mut real = vec![0u8; output.len()];
+    let mut decoy = vec![0u8; output.len()];
+    derive(real_pass.as_bytes(), salt, rounds, &mut real);
+    derive(decoy_pass.as_bytes(), salt, rounds, &mut decoy);
+
+    for ((out, r), d) in output.iter_mut().zip(real.iter()).zip(decoy.iter()) {
+        *out = u8::conditional_select(d, r, use_real);
+    }
+    wipe(&mut real);
+    wipe(&mut decoy);
+}
@@ -0,0 +1,38 @@
+#![cfg(all(feature="ssh-key", feature="std"))]
+use std::vec::Vec;
+
+use ssh_key::{Cipher, Kdf};
+
+use errors::Error;
+use super::derive;
+
+/// Derive the cipher key and IV for decrypting an `ssh_key::PrivateKey`,
+/// from its `kdf()` and `cipher()`.
+///
+/// `ssh-key` calls the `bcrypt-pbkdf` crate itself when its `encryption`
+/// feature is enabled, and has no way to plug in another KDF. Without that
+/// feature, this derives the same key and IV as its
+/// `Kdf::derive_key_and_iv`, which can then be passed to `Cipher::decrypt`
+/// with the key's encrypted data (`key_data().encrypted()`).
+///
+/// Returns `Err(Error::UnsupportedKdf)` if `kdf` is not bcrypt_pbkdf and
+/// `Err(Error::UnsupportedCipher)` if `cipher` does not take a key and IV
+/// (i.e. the key is not encrypted), and `Err(Error::InvalidRounds)` if the
+/// number of rounds is zero.
+pub fn derive_ssh_key_kdf(
+    passphrase: &str, kdf: &Kdf, cipher: Cipher,
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let (salt, rounds) = match *kdf {
+        Kdf::Bcrypt { ref salt, rounds } => (salt, rounds),
+        _ => Err(Error::UnsupportedKdf)?,
+    };
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    let (key_len, iv_len) = cipher.key_and_iv_size().ok_or(Error::UnsupportedCipher)?;
+
+    let mut key = vec![0u8; key_len + iv_len];
+    derive(passphrase.as_bytes(), salt, rounds, &mut key);
+    let iv = key.split_off(key_len);
+    Ok((key, iv))
+}
@@ -0,0 +1,46 @@
+#![cfg(all(feature="hmac", feature="std"))]
+use std::convert::TryFrom;
+use std::vec::Vec;
+
+use byteorder::{ByteOrder, BigEndian};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::derive;
+
+/// Derive an `out_len`-byte key with bcrypt_pbkdf, and compute an
+/// HMAC-SHA256 tag with `mac_key` over the salt, the rounds and the key, for
+/// storing them together with integrity protection.
+///
+/// The tag is over `len(salt) || salt || rounds || key`, with `len(salt)` and
+/// `rounds` as big-endian 32-bit integers. The length prefix keeps the
+/// boundary between the salt and the rest unambiguous.
+///
+/// `mac_key` must be independent of the passphrase (e.g. a random key held
+/// by the application). A MAC key derived from the passphrase would let
+/// anyone who can check a tag also check passphrase guesses against it,
+/// without paying for bcrypt_pbkdf.
+///
+/// # Panics
+/// Panics if `salt` is longer than `u32::MAX` bytes.
+pub fn bcrypt_pbkdf_tagged(
+    passphrase: &str, salt: &[u8], rounds: u32, out_len: usize, mac_key: &[u8],
+) -> (Vec<u8>, [u8; 32]) {
+    let mut key = vec![0u8; out_len];
+    derive(passphrase.as_bytes(), salt, rounds, &mut key);
+
+    let salt_len = u32::try_from(salt.len()).expect("salt longer than u32::MAX bytes");
+    let mut encoded_salt_len = [0u8; 4];
+    BigEndian::write_u32(&mut encoded_salt_len, salt_len);
+    let mut encoded_rounds = [0u8; 4];
+    BigEndian::write_u32(&mut encoded_rounds, rounds);
+    let mut mac = Hmac::<Sha256>::new_varkey(mac_key).expect("HMAC accepts all key sizes");
+    mac.input(&encoded_salt_len);
+    mac.input(salt);
+    mac.input(&encoded_rounds);
+    mac.input(&key);
+
+    let mut tag = [0u8; 32];
+    tag.copy_from_slice(&mac.result().code());
+    (key, tag)
+}
@@ -0,0 +1,143 @@
+#[cfg(feature="std")]
+use std::io::{self, Read};
+
+use sha2::{Digest, Sha512};
+use subtle::{Choice, ConstantTimeEq};
+
+use errors::Error;
+use bhash::{bhash, BHASH_OUTPUT_SIZE};
+#[cfg(feature="std")]
+use super::derive;
+use super::{derive_blockwise, generate, stride, transpose_index, wipe};
+
+/// Check whether `passphrase` derives `expected` without allocating.
+///
+/// `expected.len()` bytes are derived into `scratch` and compared against
+/// `expected` in constant time. `scratch` must be at least
+/// `32 * ceil(expected.len() / 32)` bytes long, otherwise
+/// `Err(Error::ScratchTooSmall)` is returned. Its contents are overwritten,
+/// and it may have any alignment. Zero rounds are rejected with
+/// `Err(Error::InvalidRounds)`.
+///
+/// # Arguments
+/// - `passphrase` - The passphrase to check.
+/// - `salt` - The salt value used for the expected key.
+/// - `rounds` - The number of rounds used for the expected key.
+/// - `expected` - The expected derived key.
+/// - `scratch` - Working memory for the derivation.
+pub fn verify_with_scratch(
+    passphrase: &str, salt: &[u8], rounds: u32, expected: &[u8],
+    scratch: &mut [u8],
+) -> Result<bool, Error> {
+    if rounds == 0 {
+        Err(Error::InvalidRounds)?;
+    }
+    let stride = stride(expected.len());
+    if scratch.len() < stride * BHASH_OUTPUT_SIZE {
+        Err(Error::ScratchTooSmall)?;
+    }
+    let generated = &mut scratch[..stride * BHASH_OUTPUT_SIZE];
+
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    generate::<Sha512>(&sha2_pass, salt, rounds, generated);
+    wipe(&mut sha2_pass);
+
+    // Compare through the transpose so that the derived key never has to be
+    // materialised separately.
+    let mut equal = Choice::from(1);
+    for (i, e) in expected.iter().enumerate() {
+        equal &= generated[transpose_index(i, stride)].ct_eq(e);
+    }
+    wipe(generated);
+
+    Ok(equal.unwrap_u8() == 1)
+}
+
+/// Check in constant time whether `passphrase` derives `expected`.
+///
+/// Unlike the `verify_*` functions, the result is returned as a
+/// `subtle::Choice`, so it can be combined with other constant-time checks
+/// without branching on it.
+#[cfg(feature="std")]
+pub fn bcrypt_pbkdf_ct_eq(passphrase: &str, salt: &[u8], rounds: u32, expected: &[u8]) -> Choice {
+    let mut derived = vec![0u8; expected.len()];
+    derive(passphrase.as_bytes(), salt, rounds, &mut derived);
+    let equal = derived.ct_eq(expected);
+    wipe(&mut derived);
+    equal
+}
+
+/// Check in constant time whether `passphrase` derives the `N`-byte key
+/// `expected`.
+///
+/// The length is part of the type, so exactly `N` bytes are always derived
+/// and compared, without any branch on the lengths of the inputs. The key is
+/// derived on the stack one PBKDF2 block at a time (as in
+/// `bcrypt_pbkdf_fixed`), so this does not allocate and is also available in
+/// `no_std` builds.
+pub fn verify_fixed_len<const N: usize>(
+    passphrase: &str, salt: &[u8], rounds: u32, expected: &[u8; N],
+) -> Choice {
+    let mut derived = [0u8; N];
+    let mut sha2_pass = Sha512::digest(passphrase.as_bytes());
+    derive_blockwise(salt, rounds, &mut derived, |sha2_salt| bhash(&sha2_pass, sha2_salt));
+    wipe(&mut sha2_pass);
+    let equal = derived.ct_eq(expected);
+    wipe(&mut derived);
+    equal
+}
+
+/// Check in constant time whether `pass_a` and `pass_b` derive the same
+/// `len`-byte key with `salt` and `rounds`, e.g. to refuse a "new"
+/// passphrase which is the same as the old one.
+///
+/// This costs two full derivations, twice as much as `bcrypt_pbkdf` with the
+/// same parameters. Both are always computed, whether or not the passphrases
+/// are equal. Different passphrases only derive the same key by chance, with
+/// a probability of about `2^-(8 * len)`, so `len` should not be too small;
+/// for `len == 0` this returns `true`.
+#[cfg(feature="std")]
+pub fn passphrases_equivalent(
+    pass_a: &str, pass_b: &str, salt: &[u8], rounds: u32, len: usize,
+) -> bool {
+    let mut derived_a = vec![0u8; len];
+    let mut derived_b = vec![0u8; len];
+    derive(pass_a.as_bytes(), salt, rounds, &mut derived_a);
+    derive(pass_b.as_bytes(), salt, rounds, &mut derived_b);
+    let equal = derived_a.ct_eq(&derived_b);
+    wipe(&mut derived_a);
+    wipe(&mut derived_b);
+    equal.unwrap_u8() == 1
+}
+
+/// Check whether `passphrase` derives the `len`-byte key read from
+/// `expected`.
+///
+/// The key is derived, then exactly `len` bytes are read from `expected` in
+/// small chunks and compared against it in constant time, so the expected
+/// value is never held in memory as a whole. Read errors are returned as is;
+/// if `expected` ends before `len` bytes have been read, an error of kind
+/// `UnexpectedEof` is returned. Nothing is read beyond `len` bytes.
+#[cfg(feature="std")]
+pub fn verify_from_reader<R: Read>(
+    passphrase: &str, salt: &[u8], rounds: u32, len: usize, expected: &mut R,
+) -> io::Result<bool> {
+    let mut derived = vec![0u8; len];
+    derive(passphrase.as_bytes(), salt, rounds, &mut derived);
+
+    let mut buf = [0u8; 64];
+    let mut equal = Choice::from(1);
+    let mut result = Ok(());
+    for chunk in derived.chunks(buf.len()) {
+        let buf = &mut buf[..chunk.len()];
+        if let Err(e) = expected.read_exact(buf) {
+            result = Err(e);
+            break;
+        }
+        equal &= chunk.ct_eq(buf);
+    }
+    wipe(&mut derived);
+    wipe(&mut buf);
+
+    result.map(|()| equal.unwrap_u8() == 1)
+}
@@ -0,0 +1,60 @@
+#![cfg(feature="capi")]
+extern crate bcrypt_pbkdf;
+
+use std::ptr;
+
+use bcrypt_pbkdf::capi::*;
+
+fn call(pass: &[u8], salt: &[u8], rounds: u32, out: &mut [u8]) -> i32 {
+    unsafe {
+        bcrypt_pbkdf_c(pass.as_ptr(), pass.len(), salt.as_ptr(), salt.len(), rounds,
+            out.as_mut_ptr(), out.len())
+    }
+}
+
+#[test]
+fn test_capi_vectors() {
+    // From the OpenBSD regression tests for bcrypt_pbkdf.
+    let mut out = [0u8; 32];
+    assert_eq!(call(b"password", b"salt", 4, &mut out), 0);
+    assert_eq!(out, [
+        0x5b, 0xbf, 0x0c, 0xc2, 0x93, 0x58, 0x7f, 0x1c,
+        0x36, 0x35, 0x55, 0x5c, 0x27, 0x79, 0x65, 0x98,
+        0xd4, 0x7e, 0x57, 0x90, 0x71, 0xbf, 0x42, 0x7e,
+        0x9d, 0x8f, 0xbe, 0x84, 0x2a, 0xba, 0x34, 0xd9 ]);
+
+    let mut out = [0u8; 16];
+    assert_eq!(call(b"pass\x00word", b"sa\x00lt", 4, &mut out), 0);
+    assert_eq!(out, [
+        0x4b, 0xa4, 0xac, 0x39, 0x25, 0xc0, 0xe8, 0xd7,
+        0xf0, 0xcd, 0xb6, 0xbb, 0x16, 0x84, 0xa5, 0x6f ]);
+}
+
+#[test]
+fn test_capi_invalid() {
+    let mut out = [0xaau8; 16];
+    assert_eq!(call(b"", b"salt", 4, &mut out), BCRYPT_PBKDF_C_EMPTY_PASSPHRASE);
+    assert_eq!(call(b"password", b"", 4, &mut out), BCRYPT_PBKDF_C_INVALID_SALT_LEN);
+    assert_eq!(call(b"password", &vec![0; (1 << 20) + 1], 4, &mut out),
+        BCRYPT_PBKDF_C_INVALID_SALT_LEN);
+    assert_eq!(call(b"password", b"salt", 0, &mut out), BCRYPT_PBKDF_C_INVALID_ROUNDS);
+    assert_eq!(call(b"password", b"salt", 4, &mut []), BCRYPT_PBKDF_C_INVALID_OUTPUT_LEN);
+    assert_eq!(call(b"password", b"salt", 4, &mut [0; 1025]), BCRYPT_PBKDF_C_INVALID_OUTPUT_LEN);
+    assert_eq!(out, [0xaau8; 16]);
+
+    unsafe {
+        assert_eq!(bcrypt_pbkdf_c(ptr::null(), 8, b"salt".as_ptr(), 4, 4, out.as_mut_ptr(), 16),
+            BCRYPT_PBKDF_C_NULL_POINTER);
+        assert_eq!(bcrypt_pbkdf_c(b"password".as_ptr(), 8, ptr::null(), 4, 4,
+            out.as_mut_ptr(), 16), BCRYPT_PBKDF_C_NULL_POINTER);
+        assert_eq!(bcrypt_pbkdf_c(b"password".as_ptr(), 8, b"salt".as_ptr(), 4, 4,
+            ptr::null_mut(), 16), BCRYPT_PBKDF_C_NULL_POINTER);
+
+        // Null pointers with a zero length are empty inputs.
+        assert_eq!(bcrypt_pbkdf_c(ptr::null(), 0, b"salt".as_ptr(), 4, 4, out.as_mut_ptr(), 16),
+            BCRYPT_PBKDF_C_EMPTY_PASSPHRASE);
+        assert_eq!(bcrypt_pbkdf_c(b"password".as_ptr(), 8, b"salt".as_ptr(), 4, 4,
+            ptr::null_mut(), 0), BCRYPT_PBKDF_C_INVALID_OUTPUT_LEN);
+    }
+    assert_eq!(out, [0xaau8; 16]);
+}
@@ -0,0 +1,91 @@
+//! Runs the examples, which `cargo test` builds alongside the tests.
+#![cfg(feature="std")]
+extern crate bcrypt_pbkdf;
+extern crate serde_json;
+
+use std::env;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use bcrypt_pbkdf::bcrypt_pbkdf;
+use serde_json::Value;
+
+fn run(example: &str, args: &[&str], stdin: &str) -> Output {
+    // Test binaries live in target/<profile>/deps, examples in
+    // target/<profile>/examples.
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    path.pop();
+    path.push("examples");
+    path.push(format!("{}{}", example, env::consts::EXE_SUFFIX));
+
+    let mut child = Command::new(&path)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| panic!("could not run {}: {}", path.display(), e));
+    // The example exits without reading its input when the arguments are bad.
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_derive_example() {
+    // The first OpenBSD test vector.
+    let out = run("derive", &["73616c74", "4", "32"], "password\n");
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "5bbf0cc293587f1c3635555c27796598d47e579071bf427e9d8fbe842aba34d9\n");
+}
+
+#[test]
+fn test_derive_example_bad_args() {
+    let out = run("derive", &["not hex", "4", "32"], "password\n");
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8(out.stderr).unwrap().contains("salt is not valid hex"));
+
+    let out = run("derive", &["73616c74"], "password\n");
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn test_genvec_example() {
+    let out = run("genvec", &["00736100", "5", "40"], "pass\u{0}word\n");
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    let mut expected = [0u8; 40];
+    bcrypt_pbkdf("pass\0word", b"\0sa\0", 5, &mut expected);
+    let expected_hex: String = expected.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let json: Value = serde_json::from_str(lines.last().unwrap()).unwrap();
+    assert_eq!(json["passphrase"], "pass\0word");
+    assert_eq!(json["salt_hex"], "00736100");
+    assert_eq!(json["rounds"], 5);
+    assert_eq!(json["out_len"], 40);
+    assert_eq!(json["expected_hex"], expected_hex.as_str());
+
+    // The Rust entry has the same values.
+    assert_eq!(lines[0], "        Test {");
+    assert_eq!(lines[1], "            password: \"pass\\0word\",");
+    assert_eq!(lines[3], "                0x00, 0x73, 0x61, 0x00 ],");
+    assert_eq!(lines[4], "            rounds: 5,");
+    let rust_out: Vec<u8> = lines[6..11].iter()
+        .flat_map(|line| line.split([',', ' ', ']']))
+        .filter(|b| !b.is_empty())
+        .map(|b| u8::from_str_radix(b.trim_start_matches("0x"), 16).unwrap())
+        .collect();
+    assert_eq!(rust_out[..], expected[..]);
+}
+
+#[test]
+fn test_genvec_example_zero_rounds() {
+    let out = run("genvec", &["73616c74", "0", "32"], "password\n");
+    assert_eq!(out.status.code(), Some(2));
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.starts_with("error: rounds must not be zero\n"), "{}", stderr);
+}
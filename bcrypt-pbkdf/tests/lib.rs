@@ -0,0 +1,2358 @@
+#![cfg(feature="std")]
+extern crate bcrypt_pbkdf;
+#[cfg(feature="hmac")]
+extern crate hmac;
+#[cfg(feature="phc")]
+extern crate password_hash;
+#[cfg(feature="internals")]
+extern crate pbkdf2;
+#[cfg(feature="rand_core")]
+extern crate rand_core;
+extern crate serde;
+extern crate serde_json;
+extern crate sha2;
+#[cfg(feature="ssh-key")]
+extern crate ssh_key;
+extern crate subtle;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::Deserialize;
+use sha2::Sha512;
+use bcrypt_pbkdf::{
+    bcrypt_pbkdf, bcrypt_pbkdf_blocks, bcrypt_pbkdf_budgeted, bcrypt_pbkdf_cancellable,
+    bcrypt_pbkdf_chunked, bcrypt_pbkdf_cow, bcrypt_pbkdf_ct_eq, bcrypt_pbkdf_detailed,
+    bcrypt_pbkdf_emit, bcrypt_pbkdf_exact, bcrypt_pbkdf_fixed, bcrypt_pbkdf_in_place,
+    bcrypt_pbkdf_key_iv, bcrypt_pbkdf_on_block, bcrypt_pbkdf_openssh, bcrypt_pbkdf_redundant,
+    bcrypt_pbkdf_select, bcrypt_pbkdf_then, bcrypt_pbkdf_u32, bcrypt_pbkdf_vectored,
+    bcrypt_pbkdf_with_digest, bcrypt_pbkdf_write, bcrypt_pbkdf_write_n, bcrypt_pbkdf_xor,
+    checked_bcrypt_pbkdf, derive_lengths, derive_multi_salt, derive_shared,
+    derive_with_salt_source, derive_within_budget, equivalent_pbkdf2_iterations, estimate_ops,
+    passphrase_prehash, passphrases_equivalent, time_rounds, verify_fixed_len,
+    verify_from_reader, verify_with_scratch, BcryptPbkdf, BcryptPbkdfBuilder,
+    DerivationCheckpoint, Error, FixedSaltCtx, GridPool, Kdf, Params, PreparedPassphrase,
+    SaltSource, SaltedParams, WorkBudget, DEFAULT_MAX_PASSPHRASE_LEN, DEFAULT_MAX_SALT_LEN,
+    MAX_OUTPUT_LEN, OPENSSH_MAX_ROUNDS, OPENSSH_MIN_ROUNDS,
+};
+
+struct Test {
+    password: &'static str,
+    salt: Vec<u8>,
+    rounds: u32,
+    out: Vec<u8>,
+}
+
+// Test vectors from the OpenBSD regression tests for bcrypt_pbkdf.
+fn tests() -> Vec<Test> {
+    vec![
+        Test {
+            password: "password",
+            salt: b"salt".to_vec(),
+            rounds: 4,
+            out: vec![
+                0x5b, 0xbf, 0x0c, 0xc2, 0x93, 0x58, 0x7f, 0x1c,
+                0x36, 0x35, 0x55, 0x5c, 0x27, 0x79, 0x65, 0x98,
+                0xd4, 0x7e, 0x57, 0x90, 0x71, 0xbf, 0x42, 0x7e,
+                0x9d, 0x8f, 0xbe, 0x84, 0x2a, 0xba, 0x34, 0xd9 ]
+        },
+        Test {
+            password: "password",
+            salt: vec![0],
+            rounds: 4,
+            out: vec![
+                0xc1, 0x2b, 0x56, 0x62, 0x35, 0xee, 0xe0, 0x4c,
+                0x21, 0x25, 0x98, 0x97, 0x0a, 0x57, 0x9a, 0x67 ]
+        },
+        Test {
+            password: "\x00",
+            salt: b"salt".to_vec(),
+            rounds: 4,
+            out: vec![
+                0x60, 0x51, 0xbe, 0x18, 0xc2, 0xf4, 0xf8, 0x2c,
+                0xbf, 0x0e, 0xfe, 0xe5, 0x47, 0x1b, 0x4b, 0xb9 ]
+        },
+        Test {
+            password: "password\x00",
+            salt: b"salt\x00".to_vec(),
+            rounds: 4,
+            out: vec![
+                0x74, 0x10, 0xe4, 0x4c, 0xf4, 0xfa, 0x07, 0xbf,
+                0xaa, 0xc8, 0xa9, 0x28, 0xb1, 0x72, 0x7f, 0xac,
+                0x00, 0x13, 0x75, 0xe7, 0xbf, 0x73, 0x84, 0x37,
+                0x0f, 0x48, 0xef, 0xd1, 0x21, 0x74, 0x30, 0x50 ]
+        },
+        Test {
+            password: "pass\x00wor",
+            salt: b"sa\x00l".to_vec(),
+            rounds: 4,
+            out: vec![
+                0xc2, 0xbf, 0xfd, 0x9d, 0xb3, 0x8f, 0x65, 0x69,
+                0xef, 0xef, 0x43, 0x72, 0xf4, 0xde, 0x83, 0xc0 ]
+        },
+        Test {
+            password: "pass\x00word",
+            salt: b"sa\x00lt".to_vec(),
+            rounds: 4,
+            out: vec![
+                0x4b, 0xa4, 0xac, 0x39, 0x25, 0xc0, 0xe8, 0xd7,
+                0xf0, 0xcd, 0xb6, 0xbb, 0x16, 0x84, 0xa5, 0x6f ]
+        },
+        Test {
+            password: "password",
+            salt: b"salt".to_vec(),
+            rounds: 8,
+            out: vec![
+                0xe1, 0x36, 0x7e, 0xc5, 0x15, 0x1a, 0x33, 0xfa,
+                0xac, 0x4c, 0xc1, 0xc1, 0x44, 0xcd, 0x23, 0xfa,
+                0x15, 0xd5, 0x54, 0x84, 0x93, 0xec, 0xc9, 0x9b,
+                0x9b, 0x5d, 0x9c, 0x0d, 0x3b, 0x27, 0xbe, 0xc7,
+                0x62, 0x27, 0xea, 0x66, 0x08, 0x8b, 0x84, 0x9b,
+                0x20, 0xab, 0x7a, 0xa4, 0x78, 0x01, 0x02, 0x46,
+                0xe7, 0x4b, 0xba, 0x51, 0x72, 0x3f, 0xef, 0xa9,
+                0xf9, 0x47, 0x4d, 0x65, 0x08, 0x84, 0x5e, 0x8d ]
+        },
+        Test {
+            password: "password",
+            salt: b"salt".to_vec(),
+            rounds: 42,
+            out: vec![
+                0x83, 0x3c, 0xf0, 0xdc, 0xf5, 0x6d, 0xb6, 0x56,
+                0x08, 0xe8, 0xf0, 0xdc, 0x0c, 0xe8, 0x82, 0xbd ]
+        },
+        Test {
+            password: "Lorem ipsum dolor sit amet, consectetur adipisicing elit, \
+                sed do eiusmod tempor incididunt ut labore et dolore magna \
+                aliqua. Ut enim ad minim veniam, quis nostrud exercitation \
+                ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis \
+                aute irure dolor in reprehenderit in voluptate velit esse \
+                cillum dolore eu fugiat nulla pariatur. Excepteur sint \
+                occaecat cupidatat non proident, sunt in culpa qui officia \
+                deserunt mollit anim id est laborum.",
+            salt: b"salis\x00".to_vec(),
+            rounds: 8,
+            out: vec![
+                0x10, 0x97, 0x8b, 0x07, 0x25, 0x3d, 0xf5, 0x7f,
+                0x71, 0xa1, 0x62, 0xeb, 0x0e, 0x8a, 0xd3, 0x0a ]
+        },
+        // Output lengths which are not a multiple of 32, generated with the
+        // `bcrypt-pbkdf` crate from crates.io.
+        Test {
+            password: "password",
+            salt: b"salt".to_vec(),
+            rounds: 8,
+            out: vec![
+                0xe1, 0x36, 0x7e, 0xc5, 0x15, 0x1a, 0x33, 0xfa,
+                0xac, 0x4c, 0xc1, 0xc1, 0x44, 0xcd, 0x23, 0xfa,
+                0x15, 0xd5, 0x54, 0x84, 0x93, 0xec, 0xc9, 0x9b,
+                0x9b, 0x5d, 0x9c, 0x0d, 0x3b, 0x27, 0xbe, 0xc7,
+                0x62 ]
+        },
+        Test {
+            password: "passphrase",
+            salt: b"NaCl".to_vec(),
+            rounds: 5,
+            out: vec![
+                0xda, 0xac, 0xc0, 0xdc, 0x1a, 0xbe, 0xb7, 0x2d,
+                0x11, 0x4f, 0x15, 0xd2, 0xf4, 0xff, 0xf9, 0xf2,
+                0xd9, 0xd9, 0xed, 0xae, 0x7e, 0x1d, 0x25, 0x52,
+                0x38, 0x17, 0x3d, 0x6a, 0xfc, 0xc5, 0x02, 0xb6,
+                0x1f, 0xba, 0x94, 0x93, 0xb4, 0x0a, 0x1b, 0x9f,
+                0xb2, 0xc9, 0xe5, 0x5a, 0x99, 0xdd, 0x28 ]
+        },
+        Test {
+            password: "open sesame",
+            salt: (0..16).collect(),
+            rounds: 6,
+            out: vec![
+                0xf5, 0x45, 0x13, 0x83, 0xf0, 0xdb, 0xf7, 0xd7,
+                0xc4, 0xe7, 0xa1, 0x9e, 0xa8, 0x6b, 0x6c, 0x3d,
+                0x39, 0x92, 0x53, 0x59, 0x5e, 0x64, 0xa1, 0x08,
+                0x94, 0xb7, 0xcc, 0x1a, 0xb9, 0xae, 0x46, 0x42,
+                0x65, 0xf6, 0xa0, 0xa7, 0x07, 0x3a, 0xc9, 0xc0,
+                0x3c, 0xf4, 0xe0, 0x20, 0xb3, 0x89, 0xac, 0xd6,
+                0xbe, 0x8e, 0xce, 0x69, 0x5c, 0x62, 0x3d, 0xda,
+                0xca, 0xfb, 0x78, 0xb7, 0x56, 0xf8, 0x67 ]
+        },
+    ]
+}
+
+/// Minimal known-answer test: the simplest OpenBSD vector, with a single
+/// PBKDF2 block. The test harness has no way to order tests, but if this
+/// fails (e.g. because of a miscompilation of the Blowfish or SHA-512 code)
+/// the failures of every other test follow from it, so look at it first.
+/// The seed loading part of `bhash` is also checked at compile time.
+#[test]
+fn kat_smoke() {
+    let mut out = [0u8; 32];
+    bcrypt_pbkdf("password", b"salt", 4, &mut out);
+    assert_eq!(out[..], tests()[0].out[..]);
+}
+
+#[test]
+fn test_openbsd_vectors() {
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        bcrypt_pbkdf(t.password, &t.salt, t.rounds, &mut out);
+        assert_eq!(out, t.out);
+    }
+}
+
+/// Outputs of 64, 96 and 128 bytes need 2, 3 and 4 PBKDF2 blocks, hashed
+/// with the salt followed by the big-endian counters `00000001` to
+/// `00000004`. Byte `i` of an `n`-block output comes from the block with
+/// counter `i % n + 1`, so a mistake in the counter of any block but the
+/// first (the only one used by outputs of up to 32 bytes) shows up here, e.g.
+/// in bytes 1, 2 and 3 of the 128-byte output.
+/// Generated with the `bcrypt-pbkdf` crate from crates.io.
+#[test]
+fn test_block_counter_vectors() {
+    let vectors = [
+        (64, "792f850d3ee259a0a7fdb84ea9e4952976734a086e40f5854c5177f99d16ffa1\
+              f3503e8f09e222f0874c8108a09e5f9ca95452ea5badcf1251cdf71954a4b296"),
+        (96, "792f87850d303ee20e59a087a7fd12b84e7ba9e4f295296976737e4a08f66e40\
+              85f585ff4c51ec77f9ba9d16d9ffa1e5f350823e8fbf09e2bc22f043874cc281\
+              08e5a09edc5f9cc7a954a152eadc5bad24cf125051cdd7f7197b54a4f1b2969f"),
+        (128, "792f87da850d30293ee20edc59a08770a7fd128bb84e7bc5a9e4f24795296903\
+               76737e254a08f68a6e4085a9f585ff5c4c51ec8777f9ba8e9d16d941ffa1e5bd\
+               f350828d3e8fbfca09e2bcde22f043ac874cc2f78108e5b4a09edc265f9cc77d\
+               a954a17b52eadc155bad24decf12506351cdd724f7197b7c54a4f1abb2969f6a"),
+    ];
+    for &(len, hex) in vectors.iter() {
+        let hex: String = hex.split_whitespace().collect();
+        let mut out = vec![0u8; len];
+        bcrypt_pbkdf("counter", b"blocks", 3, &mut out);
+        assert_eq!(out, decode_hex(&hex), "len {}", len);
+    }
+}
+
+/// A 100-byte output: four PBKDF2 blocks, the last of which only provides
+/// every fourth byte of the first 100. CI runs the tests both with and
+/// without `--release`, so this pins the output at both optimization levels,
+/// through both the `std` derivation and the blockwise one used without it.
+/// Generated with the `bcrypt-pbkdf` crate from crates.io.
+#[test]
+fn test_bit_exact_multi_block() {
+    let expected = decode_hex(
+        "b51a03698692f2d8a87daf6cb513e7678a543224042931a4d36e7d4e85b73359\
+         dc1dbeaa338606589c62fdd271e15fb882a3ec07379c0e68a1b48066d4f413da\
+         ae2cd3293da4e8ae51df9919494a3c647ef91c1dd30fd0546c448827d6062fd7\
+         ff2b0b68");
+    let salt: [u8; 16] = std::array::from_fn(|i| i as u8);
+
+    let mut out = vec![0u8; 100];
+    bcrypt_pbkdf("bit-exact", &salt, 3, &mut out);
+    assert_eq!(out, expected);
+
+    let out: [u8; 100] = bcrypt_pbkdf_fixed(b"bit-exact", &salt, 3).unwrap();
+    assert_eq!(out[..], expected[..]);
+}
+
+/// A canary for gross failures such as an output buffer left unwritten: for
+/// nonzero inputs and at least one round, no aligned 32-byte window of the
+/// output (and no output at all) is entirely zero. Outputs start zeroed, so
+/// a skipped block would show up. An honest all-zero window has probability
+/// 2^-256 per window.
+#[test]
+fn test_no_all_zero_output() {
+    fn check(out: &[u8], what: &str) {
+        assert!(out.iter().any(|&b| b != 0), "{}: all zero", what);
+        for (i, window) in out.chunks_exact(32).enumerate() {
+            assert!(window.iter().any(|&b| b != 0), "{}: window {} all zero", what, i);
+        }
+    }
+
+    let mut scratch = [0u8; 160];
+    for &passphrase in ["p", "\u{1f511}", "a much longer passphrase"].iter() {
+        for &salt in [&[1][..], &[0xff; 16], &[7; 200]].iter() {
+            for &rounds in [1, 2].iter() {
+                for &len in [32usize, 65, 160].iter() {
+                    let what = format!("{:?} {:?} {} {}", passphrase, salt, rounds, len);
+
+                    let mut out = vec![0u8; len];
+                    bcrypt_pbkdf(passphrase, salt, rounds, &mut out);
+                    check(&out, &what);
+
+                    let mut in_place = vec![0u8; len];
+                    bcrypt_pbkdf_in_place(passphrase, salt, rounds, &mut in_place, &mut scratch)
+                        .unwrap();
+                    assert_eq!(in_place, out, "{}", what);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_sha512_block_boundary_salts() {
+    // PBKDF2 hashes the salt followed by the 4-byte block counter. These salt
+    // lengths put that input just around the last length that fits in one
+    // padded SHA-512 block (111 bytes) and around the 128-byte block size.
+    let vectors = [
+        (107, "9308639f5fdb4a82e5e465f6a3ff06a9f799e824b00fd841e65be3ba5b9a7505\
+               25b435ad5ac85a544e47b44c3c1073a5136944d86132707d02d0f9f02d22ac52"),
+        (108, "c697ea0adc3ccd2f1d983f09f245889b5c4c3926f19c92a337c16241184adefa\
+               e831ea78745e49019d685ea5e6e2c6500a1c78da9505c03d2c2bb480de260734"),
+        (109, "b5855330883ec0feae7cca8580079277e14c9b4f805ea418e7d9fdcd993c2a29\
+               e3afb189640bf72b697a6814d7a6122675321f6895d2cdb26b64dd11946fdaf0"),
+        (127, "ff964fc5141cc4c89c4feb30e2d6c3fa74c65c79f32e17897a35dea872fecba4\
+               457a882375b566634e5bf1be1720780ca84767a32df08e5f710637a61ab7d521"),
+        (128, "73db02d5f7aa4013d626fb983b7e9896acad8571a8d85243f395eaf78f1b55bb\
+               6981bbacb578da8b6a101149b9a6a9d2fec9e3933c8dfcb8a8d1ea0dc8905140"),
+        (129, "f06c4cd47005006e446bdbc6ba7b75ed6c5e5a771eba3a0ea10d0492c4b715f6\
+               e6ab38a11e774bf6da1ad96d8afd6855275723cc3348e5b33eb04ba8f2b4c8dc"),
+    ];
+    for &(salt_len, hex) in vectors.iter() {
+        let hex: String = hex.split_whitespace().collect();
+        let salt: Vec<u8> = (0..salt_len).map(|i| i as u8).collect();
+        let mut out = [0u8; 64];
+        bcrypt_pbkdf("boundary", &salt, 2, &mut out);
+        assert_eq!(out[..], decode_hex(&hex)[..], "salt len {}", salt_len);
+
+        // The salt hash state is also precomputed and reused across counters.
+        let mut prepared = [0u8; 64];
+        FixedSaltCtx::new(&salt, 2).derive_candidate("boundary", &mut prepared);
+        assert_eq!(prepared, out, "salt len {}", salt_len);
+    }
+}
+
+#[test]
+fn test_embedded_nul_passphrase() {
+    // The whole passphrase is hashed, so the bytes after an interior NUL must
+    // still affect the output (unlike a C API taking a NUL-terminated string).
+    let mut with_nul = [0u8; 32];
+    let mut truncated = [0u8; 32];
+    bcrypt_pbkdf("pass\x00word", b"salt", 4, &mut with_nul);
+    bcrypt_pbkdf("pass", b"salt", 4, &mut truncated);
+    assert_ne!(with_nul, truncated);
+
+    let mut other_suffix = [0u8; 32];
+    bcrypt_pbkdf("pass\x00wore", b"salt", 4, &mut other_suffix);
+    assert_ne!(with_nul, other_suffix);
+}
+
+// Reference outputs computed with the `bcrypt-pbkdf` crate from crates.io.
+#[test]
+fn test_long_salt() {
+    let salt: Vec<u8> = (0..=255).collect();
+
+    let mut out = [0u8; 32];
+    bcrypt_pbkdf("password", &salt[..128], 4, &mut out);
+    assert_eq!(out[..], decode_hex(
+        "c2ac33ac1409694ab1802a0659da55419858a1622995191ab132263140ffb98c")[..]);
+
+    let mut out = [0u8; 32];
+    bcrypt_pbkdf("password", &salt, 4, &mut out);
+    assert_eq!(out[..], decode_hex(
+        "26c47b5bb043627b00cfc43fa0585dc1d85db9ed08ccf21f8d829ad5b5e2710d")[..]);
+
+    // The last salt byte still affects the output.
+    let mut changed = salt.clone();
+    changed[255] ^= 1;
+    let mut other = [0u8; 32];
+    bcrypt_pbkdf("password", &changed, 4, &mut other);
+    assert_ne!(out, other);
+}
+
+#[test]
+fn test_builder() {
+    let key = BcryptPbkdfBuilder::new()
+        .passphrase("password")
+        .salt(b"salt")
+        .rounds(4)
+        .output_len(32)
+        .build_and_derive()
+        .unwrap();
+    assert_eq!(key, tests()[0].out);
+
+    let mut expected = [0u8; 48];
+    bcrypt_pbkdf("builder", b"NaCl", 6, &mut expected);
+    let key = BcryptPbkdfBuilder::new()
+        .output_len(48)
+        .rounds(6)
+        .salt(b"NaCl")
+        .passphrase("builder")
+        .build_and_derive()
+        .unwrap();
+    assert_eq!(&key[..], &expected[..]);
+}
+
+#[test]
+fn test_builder_zeroize() {
+    let key = BcryptPbkdfBuilder::new()
+        .passphrase("password")
+        .salt(b"salt")
+        .rounds(4)
+        .output_len(32)
+        .zeroize(true)
+        .build_and_derive()
+        .unwrap();
+    assert_eq!(key, tests()[0].out);
+}
+
+#[test]
+fn test_builder_missing_field() {
+    assert_eq!(
+        BcryptPbkdfBuilder::new().salt(b"salt").rounds(4).output_len(32)
+            .build_and_derive(),
+        Err(Error::MissingField("passphrase")));
+    assert_eq!(
+        BcryptPbkdfBuilder::new().passphrase("password").rounds(4)
+            .output_len(32).build_and_derive(),
+        Err(Error::MissingField("salt")));
+    assert_eq!(
+        BcryptPbkdfBuilder::new().passphrase("password").salt(b"salt")
+            .output_len(32).build_and_derive(),
+        Err(Error::MissingField("rounds")));
+    assert_eq!(
+        BcryptPbkdfBuilder::new().passphrase("password").salt(b"salt")
+            .rounds(4).build_and_derive(),
+        Err(Error::MissingField("output_len")));
+}
+
+#[test]
+fn test_derive_lengths() {
+    let lengths = [16, 32, 33, 65];
+    let prepared = PreparedPassphrase::new("password");
+    let keys = derive_lengths(&prepared, b"salt", 4, &lengths);
+
+    assert_eq!(keys.len(), lengths.len());
+    for (key, &len) in keys.iter().zip(lengths.iter()) {
+        let mut expected = vec![0u8; len];
+        bcrypt_pbkdf("password", b"salt", 4, &mut expected);
+        assert_eq!(*key, expected);
+    }
+
+    // Outputs are only prefixes of one another while the number of 32-byte
+    // blocks stays the same.
+    assert_eq!(&keys[1][..16], &keys[0][..]);
+    assert_ne!(&keys[2][..32], &keys[1][..]);
+    assert_ne!(&keys[3][..33], &keys[2][..]);
+}
+
+#[test]
+fn test_derive_multi_salt() {
+    let salts: [&[u8]; 4] = [b"salt", b"", &[0], b"another salt"];
+    let prepared = PreparedPassphrase::new("password");
+
+    for &len in [16usize, 47, 64].iter() {
+        let keys = derive_multi_salt(&prepared, &salts, 4, len);
+        assert_eq!(keys.len(), salts.len());
+        for (key, salt) in keys.iter().zip(salts.iter()) {
+            let mut expected = vec![0u8; len];
+            bcrypt_pbkdf("password", salt, 4, &mut expected);
+            assert_eq!(*key, expected);
+        }
+    }
+    assert!(derive_multi_salt(&prepared, &[], 4, 32).is_empty());
+}
+
+#[test]
+fn test_passphrase_prehash() {
+    for t in tests().iter() {
+        let prehash = passphrase_prehash(t.password.as_bytes());
+        let mut out = vec![0u8; t.out.len()];
+        derive_shared(&prehash, &t.salt, t.rounds, &mut out);
+        assert_eq!(out, t.out);
+    }
+}
+
+#[test]
+fn test_derive_shared() {
+    use sha2::Digest;
+
+    let mut prehash = [0u8; 64];
+    prehash.copy_from_slice(&Sha512::digest(b"password"));
+    assert_eq!(passphrase_prehash(b"password"), prehash);
+    let prehash = Arc::new(prehash);
+
+    let handles: Vec<_> = tests().into_iter().filter(|t| t.password == "password").map(|t| {
+        let prehash = Arc::clone(&prehash);
+        thread::spawn(move || {
+            let mut out = vec![0u8; t.out.len()];
+            derive_shared(&prehash, &t.salt, t.rounds, &mut out);
+            assert_eq!(out, t.out);
+        })
+    }).collect();
+    assert!(handles.len() > 1);
+    for h in handles {
+        h.join().unwrap();
+    }
+}
+
+#[test]
+fn test_fixed_salt_ctx() {
+    let ctx = FixedSaltCtx::new(b"salt", 4);
+    for &passphrase in ["password", "", "pass\0word", "hunter2"].iter() {
+        for &len in [16usize, 32, 47, 96].iter() {
+            let mut out = vec![0u8; len];
+            ctx.derive_candidate(passphrase, &mut out);
+            let mut expected = vec![0u8; len];
+            bcrypt_pbkdf(passphrase, b"salt", 4, &mut expected);
+            assert_eq!(out, expected, "{:?} len {}", passphrase, len);
+        }
+    }
+
+    let mut out = [0u8; 32];
+    ctx.derive_candidate("password", &mut out);
+    assert_eq!(out[..], tests()[0].out[..]);
+}
+
+#[test]
+fn test_prepared_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PreparedPassphrase>();
+
+    let prepared = Arc::new(PreparedPassphrase::new("shared"));
+    let handles: Vec<_> = (0..4u8).map(|i| {
+        let prepared = prepared.clone();
+        thread::spawn(move || {
+            let salt = [i; 16];
+            let mut out = [0u8; 48];
+            prepared.derive(&salt, 4, &mut out);
+            (salt, out)
+        })
+    }).collect();
+    for h in handles {
+        let (salt, out) = h.join().unwrap();
+        let mut expected = [0u8; 48];
+        bcrypt_pbkdf("shared", &salt, 4, &mut expected);
+        assert_eq!(out[..], expected[..]);
+    }
+}
+
+#[test]
+fn test_budgeted() {
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        let res = bcrypt_pbkdf_budgeted(
+            t.password, &t.salt, t.rounds, &mut out, Duration::from_secs(3600));
+        assert_eq!(res, Ok(()));
+        assert_eq!(out, t.out);
+    }
+
+    // This would take days to complete.
+    let mut out = [0xffu8; 32];
+    let res = bcrypt_pbkdf_budgeted(
+        "password", b"salt", u32::MAX, &mut out, Duration::from_millis(50));
+    assert_eq!(res, Err(Error::TimeBudgetExceeded));
+    assert_eq!(out, [0u8; 32]);
+}
+
+#[test]
+fn test_work_budget() {
+    let t = &tests()[0];
+    let ops = estimate_ops(t.rounds, t.out.len());
+    let budget = WorkBudget::new(2 * ops - 1);
+
+    let mut out = [0u8; 32];
+    assert_eq!(derive_within_budget(&budget, t.password, &t.salt, t.rounds, &mut out), Ok(()));
+    assert_eq!(out[..], t.out[..]);
+    assert_eq!(budget.remaining(), ops - 1);
+
+    // Not enough is left: nothing is taken or derived.
+    let mut out = [0x5au8; 32];
+    assert_eq!(derive_within_budget(&budget, t.password, &t.salt, t.rounds, &mut out),
+        Err(Error::BudgetExhausted));
+    assert_eq!(out, [0x5au8; 32]);
+    assert_eq!(budget.remaining(), ops - 1);
+
+    budget.refill(1);
+    assert_eq!(derive_within_budget(&budget, t.password, &t.salt, t.rounds, &mut out), Ok(()));
+    assert_eq!(budget.remaining(), 0);
+    budget.refill(u64::MAX);
+    budget.refill(1);
+    assert_eq!(budget.remaining(), u64::MAX);
+}
+
+#[test]
+fn test_work_budget_concurrent() {
+    let ops = estimate_ops(1, 32);
+    let budget = Arc::new(WorkBudget::new(5 * ops + ops / 2));
+
+    let handles: Vec<_> = (0..12).map(|i| {
+        let budget = budget.clone();
+        thread::spawn(move || {
+            let mut out = [0u8; 32];
+            derive_within_budget(&budget, &format!("passphrase {}", i), b"salt", 1, &mut out)
+        })
+    }).collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+    assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 5);
+    assert!(results.iter().all(|r| r.is_ok() || *r == Err(Error::BudgetExhausted)));
+    assert_eq!(budget.remaining(), ops / 2);
+}
+
+#[test]
+fn test_write() {
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        assert_eq!(bcrypt_pbkdf_write(t.password, &t.salt, t.rounds, &mut out), t.out.len());
+        assert_eq!(out, t.out);
+
+        let mut buffer = [0xaau8; 100];
+        let n = bcrypt_pbkdf_write_n(t.password, &t.salt, t.rounds, t.out.len(), &mut buffer);
+        assert_eq!(n, t.out.len());
+        assert_eq!(buffer[..n], t.out[..]);
+        assert!(buffer[n..].iter().all(|&b| b == 0xaa));
+    }
+    assert_eq!(bcrypt_pbkdf_write_n("password", b"salt", 4, 0, &mut [0xaa; 4]), 0);
+}
+
+#[test]
+#[should_panic]
+fn test_write_n_too_long() {
+    bcrypt_pbkdf_write_n("password", b"salt", 4, 33, &mut [0; 32]);
+}
+
+#[test]
+fn test_xor() {
+    for t in tests().iter() {
+        let original: Vec<u8> = (0..t.out.len()).map(|i| i as u8 ^ 0xa5).collect();
+        let mut buffer = original.clone();
+        bcrypt_pbkdf_xor(t.password, &t.salt, t.rounds, &mut buffer);
+        let key: Vec<u8> = buffer.iter().zip(original.iter()).map(|(b, o)| b ^ o).collect();
+        assert_eq!(key, t.out);
+
+        bcrypt_pbkdf_xor(t.password, &t.salt, t.rounds, &mut buffer);
+        assert_eq!(buffer, original);
+    }
+}
+
+#[test]
+fn test_key_iv() {
+    for &(key_len, iv_len) in [(32, 16), (16, 16), (0, 12), (24, 0), (64, 33)].iter() {
+        let mut combined = vec![0u8; key_len + iv_len];
+        bcrypt_pbkdf("password", b"salt", 4, &mut combined);
+
+        let mut key = vec![0u8; key_len];
+        let mut iv = vec![0u8; iv_len];
+        bcrypt_pbkdf_key_iv("password", b"salt", 4, &mut key, &mut iv);
+        assert_eq!(key[..], combined[..key_len], "{} + {}", key_len, iv_len);
+        assert_eq!(iv[..], combined[key_len..], "{} + {}", key_len, iv_len);
+    }
+
+    // Not the same as two separate derivations.
+    let (mut key, mut iv) = ([0u8; 32], [0u8; 16]);
+    bcrypt_pbkdf_key_iv("password", b"salt", 4, &mut key, &mut iv);
+    let mut separate = [0u8; 32];
+    bcrypt_pbkdf("password", b"salt", 4, &mut separate);
+    assert_ne!(key, separate);
+}
+
+#[cfg(feature="zeroize")]
+#[test]
+fn test_consume_boxed() {
+    use bcrypt_pbkdf::bcrypt_pbkdf_consume_boxed;
+
+    for t in tests().iter() {
+        let passphrase: Box<[u8]> = t.password.as_bytes().into();
+        let mut out = vec![0u8; t.out.len()];
+        bcrypt_pbkdf_consume_boxed(passphrase, &t.salt, t.rounds, &mut out);
+        assert_eq!(out, t.out);
+    }
+}
+
+#[test]
+fn test_blocks() {
+    for num_blocks in 0..5 {
+        let mut expected = vec![0u8; num_blocks * 32];
+        bcrypt_pbkdf("password", b"salt", 4, &mut expected);
+
+        let blocks: Vec<[u8; 32]> = bcrypt_pbkdf_blocks("password", b"salt", 4, num_blocks)
+            .collect();
+        assert_eq!(blocks.len(), num_blocks);
+        assert_eq!(blocks.concat(), expected, "{} blocks", num_blocks);
+    }
+}
+
+#[cfg(feature="salt-registry")]
+#[test]
+fn test_salt_registry() {
+    use bcrypt_pbkdf::{bcrypt_pbkdf_new_key, SaltRegistry};
+
+    let registry = Arc::new(SaltRegistry::new());
+    assert!(registry.is_empty());
+
+    // Distinct salts, from several threads.
+    let handles: Vec<_> = (0..4u8).map(|i| {
+        let registry = registry.clone();
+        thread::spawn(move || {
+            let mut out = [0u8; 32];
+            bcrypt_pbkdf_new_key(&registry, "password", &[i; 16], 2, &mut out)
+        })
+    }).collect();
+    for handle in handles {
+        assert!(handle.join().unwrap());
+    }
+
+    // Reusing a salt is reported (only with debug assertions), but the key is
+    // still derived.
+    let t = &tests()[0];
+    let mut out = vec![0u8; t.out.len()];
+    assert!(bcrypt_pbkdf_new_key(&registry, t.password, &t.salt, t.rounds, &mut out));
+    assert_eq!(bcrypt_pbkdf_new_key(&registry, t.password, &t.salt, t.rounds, &mut out),
+        !cfg!(debug_assertions));
+    assert_eq!(out, t.out);
+    assert_eq!(registry.record(&[2; 16]), !cfg!(debug_assertions));
+    assert_eq!(registry.len(), if cfg!(debug_assertions) { 5 } else { 0 });
+}
+
+// Runs itself again in a child process, to see what is written to stderr.
+#[cfg(feature="salt-registry")]
+#[test]
+fn test_salt_registry_warning() {
+    use bcrypt_pbkdf::{bcrypt_pbkdf_new_key, SaltRegistry};
+    use std::env;
+    use std::process::Command;
+
+    const CHILD: &str = "BCRYPT_PBKDF_SALT_REGISTRY_CHILD";
+    const WARNING: &str = "warning: bcrypt_pbkdf: a salt was used for more than one key\n";
+    if let Some(reuse) = env::var_os(CHILD) {
+        let registry = SaltRegistry::new();
+        let mut out = [0u8; 32];
+        bcrypt_pbkdf_new_key(&registry, "password", &[1; 16], 2, &mut out);
+        let salt = if reuse == "1" { [1; 16] } else { [2; 16] };
+        bcrypt_pbkdf_new_key(&registry, "password", &salt, 2, &mut out);
+        return;
+    }
+
+    for &(reuse, warned) in &[("0", false), ("1", cfg!(debug_assertions))] {
+        let out = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "test_salt_registry_warning", "--nocapture"])
+            .env(CHILD, reuse)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        let stderr = String::from_utf8(out.stderr).unwrap();
+        assert_eq!(stderr.matches(WARNING).count(), warned as usize, "{}", stderr);
+    }
+}
+
+#[test]
+fn test_salt_source() {
+    struct FixedSalt(Vec<u8>);
+    impl SaltSource for FixedSalt {
+        fn salt(&self) -> Result<Vec<u8>, Error> {
+            Ok(self.0.clone())
+        }
+    }
+
+    struct Unavailable;
+    impl SaltSource for Unavailable {
+        fn salt(&self) -> Result<Vec<u8>, Error> {
+            Err(Error::SaltUnavailable)
+        }
+    }
+
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        derive_with_salt_source(t.password, &FixedSalt(t.salt.clone()), t.rounds, &mut out)
+            .unwrap();
+        assert_eq!(out, t.out);
+    }
+
+    let mut out = [0xaau8; 32];
+    assert_eq!(derive_with_salt_source("password", &Unavailable, 4, &mut out),
+        Err(Error::SaltUnavailable));
+    assert_eq!(out, [0xaa; 32]);
+}
+
+#[test]
+fn test_cow() {
+    use std::borrow::Cow;
+
+    for t in tests().iter() {
+        let mut borrowed = vec![0u8; t.out.len()];
+        bcrypt_pbkdf_cow(Cow::Borrowed(t.password), &t.salt, t.rounds, &mut borrowed);
+        assert_eq!(borrowed, t.out);
+
+        let mut owned = vec![0u8; t.out.len()];
+        bcrypt_pbkdf_cow(Cow::Owned(t.password.to_string()), &t.salt, t.rounds, &mut owned);
+        assert_eq!(owned, borrowed);
+    }
+}
+
+#[test]
+fn test_on_block() {
+    for t in tests().iter() {
+        let num_blocks = t.out.len().div_ceil(32);
+        let mut blocks = Vec::new();
+        bcrypt_pbkdf_on_block(t.password, &t.salt, t.rounds, num_blocks, |j, block| {
+            assert_eq!(j, blocks.len());
+            blocks.push(*block);
+        });
+        assert_eq!(blocks.len(), num_blocks);
+
+        // Undo the transpose by hand.
+        let mut expected = vec![0u8; num_blocks * 32];
+        bcrypt_pbkdf(t.password, &t.salt, t.rounds, &mut expected);
+        let out: Vec<u8> = (0..expected.len())
+            .map(|i| blocks[i % num_blocks][i / num_blocks])
+            .collect();
+        assert_eq!(out, expected);
+    }
+
+    bcrypt_pbkdf_on_block("password", b"salt", 4, 0, |_, _| panic!("no blocks"));
+}
+
+#[test]
+fn test_then() {
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        bcrypt_pbkdf_then(t.password, &t.salt, t.rounds, &mut out, |key| key.reverse());
+        let mut expected = t.out.clone();
+        expected.reverse();
+        assert_eq!(out, expected);
+    }
+
+    let mut calls = 0;
+    bcrypt_pbkdf_then("password", b"salt", 4, &mut [], |key| {
+        assert!(key.is_empty());
+        calls += 1;
+    });
+    assert_eq!(calls, 1);
+}
+
+#[test]
+fn test_detailed() {
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        let stride = bcrypt_pbkdf_detailed(t.password, &t.salt, t.rounds, &mut out);
+        assert_eq!(stride, t.out.len().div_ceil(32));
+        assert_eq!(out, t.out);
+    }
+    assert_eq!(bcrypt_pbkdf_detailed("password", b"salt", 4, &mut []), 0);
+}
+
+#[test]
+fn test_estimate_ops() {
+    assert_eq!(estimate_ops(1, 32), 67_465);
+    assert_eq!(estimate_ops(0, 32), estimate_ops(1, 32));
+    assert_eq!(estimate_ops(16, 48), 16 * 2 * 67_465);
+    assert_eq!(estimate_ops(16, 0), 0);
+
+    // Extreme inputs saturate instead of overflowing.
+    assert_eq!(estimate_ops(u32::MAX, MAX_OUTPUT_LEN), 67_465 * u64::from(u32::MAX) * 32);
+    assert_eq!(estimate_ops(u32::MAX, usize::MAX), u64::MAX);
+    assert_eq!(estimate_ops(1, usize::MAX), u64::MAX);
+}
+
+#[test]
+fn test_equivalent_pbkdf2_iterations() {
+    for &len in [16usize, 32, 48, 64, 100, 1024].iter() {
+        let mut previous = 0;
+        for rounds in [1u32, 2, 16, 100, 1000, u32::MAX].iter() {
+            let iterations = equivalent_pbkdf2_iterations(*rounds, len);
+            assert!(iterations > previous, "len {} rounds {}", len, rounds);
+            previous = iterations;
+        }
+    }
+    assert_eq!(equivalent_pbkdf2_iterations(1, 32), 67_465 / 8);
+    assert_eq!(equivalent_pbkdf2_iterations(16, 0), 0);
+
+    // The ratio does not overflow even when the bcrypt_pbkdf op count would.
+    assert_eq!(equivalent_pbkdf2_iterations(u32::MAX, usize::MAX),
+        equivalent_pbkdf2_iterations(u32::MAX, 64));
+    assert_eq!(equivalent_pbkdf2_iterations(u32::MAX, 64), 67_465 * u64::from(u32::MAX) / 4);
+}
+
+#[test]
+fn test_chunked() {
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        bcrypt_pbkdf_chunked(t.password, &t.salt, t.rounds, &mut out, 1, |_| {});
+        assert_eq!(out, t.out);
+    }
+
+    // 300 bytes take 10 blocks.
+    let mut expected = [0u8; 300];
+    bcrypt_pbkdf("password", b"salt", 4, &mut expected);
+    for &(blocks_per_chunk, boundaries) in [
+        (1usize, &[1usize, 2, 3, 4, 5, 6, 7, 8, 9, 10][..]),
+        (2, &[2, 4, 6, 8, 10][..]),
+        (3, &[3, 6, 9, 10][..]),
+        (9, &[9, 10][..]),
+        (10, &[10][..]),
+        (100, &[10][..]),
+        (usize::MAX, &[10][..]),
+    ].iter() {
+        let mut out = [0u8; 300];
+        let mut seen = vec![];
+        bcrypt_pbkdf_chunked("password", b"salt", 4, &mut out, blocks_per_chunk,
+            |completed| seen.push(completed));
+        assert_eq!(out[..], expected[..], "{} blocks per chunk", blocks_per_chunk);
+        assert_eq!(seen, boundaries, "{} blocks per chunk", blocks_per_chunk);
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_chunked_zero() {
+    bcrypt_pbkdf_chunked("password", b"salt", 4, &mut [0; 32], 0, |_| {});
+}
+
+#[test]
+fn test_kdf_trait_object() {
+    let kdfs: Vec<Box<dyn Kdf>> = tests().iter()
+        .map(|t| Box::new(BcryptPbkdf::new(t.rounds)) as Box<dyn Kdf>)
+        .collect();
+    for (kdf, t) in kdfs.iter().zip(tests().iter()) {
+        let mut out = vec![0u8; t.out.len()];
+        kdf.derive(t.password.as_bytes(), &t.salt, &mut out).unwrap();
+        assert_eq!(out, t.out);
+    }
+}
+
+#[test]
+fn test_select() {
+    use subtle::Choice;
+
+    let real = &tests()[0];
+    let mut out = [0u8; 32];
+    bcrypt_pbkdf_select("password", "passw0rd", Choice::from(1), b"salt", 4, &mut out);
+    assert_eq!(out[..], real.out[..]);
+
+    let mut decoy = [0u8; 32];
+    bcrypt_pbkdf("passw0rd", b"salt", 4, &mut decoy);
+    bcrypt_pbkdf_select("password", "passw0rd", Choice::from(0), b"salt", 4, &mut out);
+    assert_eq!(out, decoy);
+    assert_ne!(out[..], real.out[..]);
+}
+
+#[test]
+fn test_derive_debug() {
+    use bcrypt_pbkdf::derive_debug;
+    use sha2::{Digest, Sha256};
+
+    let t = &tests()[10];
+    let mut out = vec![0u8; t.out.len()];
+    let diagnostics = derive_debug(t.password, &t.salt, t.rounds, &mut out);
+    assert_eq!(out, t.out);
+    if !cfg!(debug_assertions) {
+        assert_eq!(diagnostics, None);
+        return;
+    }
+    let diagnostics = diagnostics.unwrap();
+    assert_eq!(diagnostics.passphrase_len, 10);
+    assert_eq!(diagnostics.salt_len, 4);
+    assert_eq!(diagnostics.salt_sha256[..], Sha256::digest(b"NaCl")[..]);
+    assert_eq!(diagnostics.rounds, 5);
+    assert_eq!(diagnostics.output_len, 47);
+    assert_eq!(diagnostics.stride, 2);
+
+    let diagnostics = derive_debug("password", b"salt", 4, &mut [0u8; 16]).unwrap();
+    assert_eq!((diagnostics.output_len, diagnostics.stride), (16, 1));
+}
+
+#[test]
+fn test_cancellable() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let cancel = AtomicBool::new(false);
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        assert_eq!(bcrypt_pbkdf_cancellable(t.password, &t.salt, t.rounds, &mut out, &cancel),
+            Ok(()));
+        assert_eq!(out, t.out);
+    }
+
+    cancel.store(true, Ordering::SeqCst);
+    let mut out = [0xffu8; 32];
+    assert_eq!(bcrypt_pbkdf_cancellable("password", b"salt", 4, &mut out, &cancel),
+        Err(Error::Cancelled));
+    assert_eq!(out, [0u8; 32]);
+
+    // Cancelled from another thread while it runs; this would take days to
+    // complete otherwise.
+    let cancel = Arc::new(AtomicBool::new(false));
+    let handle = {
+        let cancel = cancel.clone();
+        thread::spawn(move || {
+            let mut out = [0u8; 32];
+            bcrypt_pbkdf_cancellable("password", b"salt", u32::MAX, &mut out, &cancel)
+        })
+    };
+    thread::sleep(Duration::from_millis(50));
+    cancel.store(true, Ordering::SeqCst);
+    assert_eq!(handle.join().unwrap(), Err(Error::Cancelled));
+}
+
+#[test]
+fn test_ct_eq() {
+    for t in tests().iter() {
+        assert!(bool::from(bcrypt_pbkdf_ct_eq(t.password, &t.salt, t.rounds, &t.out)));
+
+        let mut wrong = t.out.clone();
+        wrong[0] ^= 0x80;
+        assert!(!bool::from(bcrypt_pbkdf_ct_eq(t.password, &t.salt, t.rounds, &wrong)));
+    }
+}
+
+#[test]
+fn test_verify_fixed_len() {
+    let t = &tests()[0];
+    let mut expected = [0u8; 32];
+    expected.copy_from_slice(&t.out);
+    assert!(bool::from(verify_fixed_len(t.password, &t.salt, t.rounds, &expected)));
+    assert!(!bool::from(verify_fixed_len("wrong", &t.salt, t.rounds, &expected)));
+
+    expected[31] ^= 1;
+    assert!(!bool::from(verify_fixed_len(t.password, &t.salt, t.rounds, &expected)));
+
+    // 47 bytes spans two blocks, with a partial second one.
+    let t = &tests()[10];
+    let mut expected = [0u8; 47];
+    expected.copy_from_slice(&t.out);
+    assert!(bool::from(verify_fixed_len(t.password, &t.salt, t.rounds, &expected)));
+    let mut prefix = [0u8; 32];
+    prefix.copy_from_slice(&t.out[..32]);
+    assert!(!bool::from(verify_fixed_len(t.password, &t.salt, t.rounds, &prefix)));
+}
+
+#[test]
+fn test_passphrases_equivalent() {
+    assert!(passphrases_equivalent("password", "password", b"salt", 4, 32));
+    assert!(!passphrases_equivalent("password", "passwore", b"salt", 4, 32));
+    assert!(!passphrases_equivalent("password", "Password", b"salt", 4, 32));
+    // The whole passphrase is hashed, so a suffix after a NUL or a trailing
+    // space still makes a difference.
+    assert!(!passphrases_equivalent("pass\x00word", "pass", b"salt", 4, 32));
+    assert!(!passphrases_equivalent("password ", "password", b"salt", 4, 32));
+    assert!(!passphrases_equivalent("", "password", b"salt", 4, 16));
+    assert!(passphrases_equivalent("", "", b"salt", 4, 16));
+}
+
+#[test]
+fn test_verify_from_reader() {
+    use std::io::{Cursor, ErrorKind};
+
+    for t in tests().iter() {
+        let mut reader = Cursor::new(t.out.clone());
+        let res = verify_from_reader(t.password, &t.salt, t.rounds, t.out.len(), &mut reader);
+        assert!(res.unwrap());
+        assert_eq!(reader.position() as usize, t.out.len());
+    }
+
+    let t = &tests()[0];
+    let mut wrong = t.out.clone();
+    wrong[31] ^= 1;
+    let res = verify_from_reader(t.password, &t.salt, t.rounds, 32, &mut Cursor::new(wrong));
+    assert!(!res.unwrap());
+
+    // Only the derived length is read.
+    let mut longer = t.out.clone();
+    longer.push(0);
+    let mut reader = Cursor::new(longer);
+    assert!(verify_from_reader(t.password, &t.salt, t.rounds, 32, &mut reader).unwrap());
+    assert_eq!(reader.position(), 32);
+
+    let mut short = Cursor::new(t.out[..31].to_vec());
+    let err = verify_from_reader(t.password, &t.salt, t.rounds, 32, &mut short).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_verify_with_scratch() {
+    for t in tests().iter() {
+        let mut scratch = [0u8; 64];
+        assert_eq!(
+            verify_with_scratch(t.password, &t.salt, t.rounds, &t.out, &mut scratch),
+            Ok(true));
+    }
+
+    let t = &tests()[0];
+    let mut wrong = t.out.clone();
+    wrong[31] ^= 1;
+    let mut scratch = [0u8; 32];
+    assert_eq!(
+        verify_with_scratch(t.password, &t.salt, t.rounds, &wrong, &mut scratch),
+        Ok(false));
+    assert_eq!(
+        verify_with_scratch("wrong", &t.salt, t.rounds, &t.out, &mut scratch),
+        Ok(false));
+}
+
+/// The scratch buffers are only used as bytes, so any alignment works.
+#[test]
+fn test_misaligned_scratch() {
+    #[repr(align(8))]
+    struct Aligned([u8; 80]);
+
+    let t = &tests()[6];
+    let mut buf = Aligned([0; 80]);
+    // Two 32-byte blocks starting at an odd address.
+    for &offset in [1, 3, 7].iter() {
+        let scratch = &mut buf.0[offset..offset + 64];
+        assert_ne!(scratch.as_ptr() as usize % 2, 0);
+        assert_eq!(
+            verify_with_scratch(t.password, &t.salt, t.rounds, &t.out, scratch), Ok(true));
+
+        let mut out = [0u8; 64];
+        bcrypt_pbkdf_in_place(t.password, &t.salt, t.rounds, &mut out, scratch).unwrap();
+        assert_eq!(out[..], t.out[..]);
+    }
+}
+
+#[test]
+fn test_verify_with_scratch_too_small() {
+    // A 64-byte key needs two 32-byte blocks of scratch.
+    let t = &tests()[6];
+    assert_eq!(t.out.len(), 64);
+    let mut scratch = [0u8; 63];
+    assert_eq!(
+        verify_with_scratch(t.password, &t.salt, t.rounds, &t.out, &mut scratch),
+        Err(Error::ScratchTooSmall));
+    assert_eq!(
+        verify_with_scratch(t.password, &t.salt, t.rounds, &t.out[..32], &mut scratch[..31]),
+        Err(Error::ScratchTooSmall));
+}
+
+#[test]
+fn test_verify_with_scratch_zero_rounds() {
+    let t = &tests()[0];
+    let mut scratch = [0xa5u8; 32];
+    assert_eq!(verify_with_scratch(t.password, &t.salt, 0, &t.out, &mut scratch),
+        Err(Error::InvalidRounds));
+    assert_eq!(scratch, [0xa5u8; 32]);
+}
+
+#[test]
+fn test_in_place() {
+    let mut scratch = [0xaau8; 100];
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        bcrypt_pbkdf_in_place(t.password, &t.salt, t.rounds, &mut out, &mut scratch).unwrap();
+        assert_eq!(out, t.out);
+    }
+
+    // Exactly enough scratch for two blocks.
+    let t = &tests()[6];
+    let mut out = [0u8; 64];
+    bcrypt_pbkdf_in_place(t.password, &t.salt, t.rounds, &mut out, &mut scratch[..64]).unwrap();
+    assert_eq!(out[..], t.out[..]);
+}
+
+#[test]
+fn test_in_place_scratch_too_small() {
+    let t = &tests()[10];
+    assert_eq!(t.out.len(), 47);
+    let mut out = [0x5au8; 47];
+    let mut scratch = [0u8; 63];
+    assert_eq!(bcrypt_pbkdf_in_place(t.password, &t.salt, t.rounds, &mut out, &mut scratch),
+        Err(Error::ScratchTooSmall));
+    assert_eq!(out, [0x5au8; 47]);
+    assert_eq!(bcrypt_pbkdf_in_place(t.password, &t.salt, t.rounds, &mut out[..1], &mut []),
+        Err(Error::ScratchTooSmall));
+
+    // An empty output needs no scratch.
+    assert_eq!(bcrypt_pbkdf_in_place(t.password, &t.salt, t.rounds, &mut [], &mut []), Ok(()));
+}
+
+#[test]
+fn test_in_place_zero_rounds() {
+    let mut out = [0x5au8; 47];
+    let mut scratch = [0xa5u8; 64];
+    assert_eq!(bcrypt_pbkdf_in_place("password", b"salt", 0, &mut out, &mut scratch),
+        Err(Error::InvalidRounds));
+    assert_eq!(out, [0x5au8; 47]);
+    assert_eq!(scratch, [0xa5u8; 64]);
+}
+
+#[test]
+fn test_with_digest() {
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        bcrypt_pbkdf_with_digest::<Sha512>(t.password, &t.salt, t.rounds, &mut out);
+        assert_eq!(out, t.out);
+    }
+}
+
+#[cfg(feature="hmac")]
+#[test]
+fn test_derive_mac_key() {
+    use bcrypt_pbkdf::derive_mac_key;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    let key = derive_mac_key::<Hmac<Sha256>>("password", b"salt", 4);
+    assert_eq!(key.len(), 64);
+    let mut expected = [0u8; 64];
+    bcrypt_pbkdf("password", b"salt", 4, &mut expected);
+    assert_eq!(key[..], expected[..]);
+
+    let mut derived = Hmac::<Sha256>::new(&key);
+    derived.input(b"message");
+    let mut manual = Hmac::<Sha256>::new_varkey(&expected).unwrap();
+    manual.input(b"message");
+    assert_eq!(derived.result().code(), manual.result().code());
+}
+
+#[cfg(feature="zeroize")]
+#[test]
+fn test_zeroizing() {
+    for t in tests().iter().take(3) {
+        let out = bcrypt_pbkdf::bcrypt_pbkdf_zeroizing(t.password, &t.salt, t.rounds, t.out.len());
+        assert_eq!(*out, t.out);
+    }
+}
+
+#[test]
+fn test_vectored() {
+    let t = &tests()[0];
+    let mut out = [0u8; 32];
+    bcrypt_pbkdf_vectored(&[b"pass", b"word"], &t.salt, t.rounds, &mut out);
+    assert_eq!(out[..], t.out[..]);
+
+    bcrypt_pbkdf_vectored(&[b"", b"pa", b"", b"ssword"], &t.salt, t.rounds, &mut out);
+    assert_eq!(out[..], t.out[..]);
+}
+
+#[test]
+fn test_u32() {
+    for t in tests().iter().take(3) {
+        let mut words = vec![0u32; t.out.len() / 4];
+        bcrypt_pbkdf_u32(t.password, &t.salt, t.rounds, &mut words);
+
+        let mut bytes = vec![0u8; words.len() * 4];
+        bcrypt_pbkdf(t.password, &t.salt, t.rounds, &mut bytes);
+        let expected: Vec<u32> = bytes.chunks(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        assert_eq!(words, expected);
+    }
+}
+
+#[test]
+fn test_checked() {
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        assert_eq!(checked_bcrypt_pbkdf(t.password, &t.salt, t.rounds, &mut out), Ok(()));
+        assert_eq!(out, t.out);
+    }
+
+    let mut out = vec![0u8; MAX_OUTPUT_LEN + 1];
+    assert_eq!(checked_bcrypt_pbkdf("password", b"salt", 0, &mut out[..32]),
+        Err(Error::InvalidRounds));
+    assert_eq!(checked_bcrypt_pbkdf("password", b"salt", 1, &mut out[..0]),
+        Err(Error::InvalidOutputLen));
+    assert_eq!(checked_bcrypt_pbkdf("password", b"salt", 1, &mut out),
+        Err(Error::InvalidOutputLen));
+
+    // The limits themselves, and empty passphrase and salt, are accepted.
+    let mut expected = vec![0u8; MAX_OUTPUT_LEN];
+    bcrypt_pbkdf("", b"", 1, &mut expected);
+    assert_eq!(checked_bcrypt_pbkdf("", b"", 1, &mut out[..MAX_OUTPUT_LEN]), Ok(()));
+    assert_eq!(out[..MAX_OUTPUT_LEN], expected[..]);
+}
+
+#[test]
+fn test_openssh_rounds_range() {
+    assert_eq!(OPENSSH_MIN_ROUNDS, 1);
+    assert_eq!(OPENSSH_MAX_ROUNDS, 2_147_483_647);
+
+    let t = &tests()[0];
+    let mut out = [0u8; 32];
+    assert_eq!(bcrypt_pbkdf_openssh(t.password, &t.salt, t.rounds, &mut out), Ok(()));
+    assert_eq!(out[..], t.out[..]);
+    let mut expected = [0u8; 32];
+    bcrypt_pbkdf("password", b"salt", OPENSSH_MIN_ROUNDS, &mut expected);
+    assert_eq!(bcrypt_pbkdf_openssh("password", b"salt", OPENSSH_MIN_ROUNDS, &mut out), Ok(()));
+    assert_eq!(out, expected);
+
+    // The rounds are checked first, so an empty output shows the maximum is
+    // accepted without running 2^31 - 1 rounds.
+    assert_eq!(bcrypt_pbkdf_openssh("password", b"salt", OPENSSH_MAX_ROUNDS, &mut []),
+        Err(Error::InvalidOutputLen));
+
+    for &rounds in [0, OPENSSH_MAX_ROUNDS + 1, u32::MAX].iter() {
+        let mut out = [0x5au8; 32];
+        assert_eq!(bcrypt_pbkdf_openssh("password", b"salt", rounds, &mut out),
+            Err(Error::RoundsOutOfRange), "rounds {}", rounds);
+        assert_eq!(out, [0x5au8; 32]);
+    }
+}
+
+#[test]
+fn test_exact() {
+    let t = &tests()[0];
+    let mut out = [0u8; 32];
+    assert_eq!(bcrypt_pbkdf_exact::<32>(t.password, &t.salt, t.rounds, &mut out), Ok(()));
+    assert_eq!(out[..], t.out[..]);
+
+    let mut out = [0u8; 31];
+    assert_eq!(
+        bcrypt_pbkdf_exact::<32>(t.password, &t.salt, t.rounds, &mut out),
+        Err(Error::LengthMismatch { expected: 32, got: 31 }));
+    assert_eq!(out, [0u8; 31]);
+}
+
+#[cfg(feature="phc")]
+#[test]
+fn test_with_saltstring() {
+    use password_hash::SaltString;
+
+    let salt = SaltString::encode_b64(b"saltsaltsalt").unwrap();
+    assert_eq!(salt.as_str(), "c2FsdHNhbHRzYWx0");
+    let salt = SaltString::from_b64(salt.as_str()).unwrap();
+
+    let mut out = [0u8; 40];
+    bcrypt_pbkdf::bcrypt_pbkdf_with_saltstring("password", salt.as_salt(), 8, &mut out).unwrap();
+    let mut expected = [0u8; 40];
+    bcrypt_pbkdf("password", b"saltsaltsalt", 8, &mut expected);
+    assert_eq!(out[..], expected[..]);
+
+    assert_eq!(bcrypt_pbkdf::bcrypt_pbkdf_with_saltstring("password", salt.as_salt(), 0,
+        &mut out), Err(Error::InvalidRounds));
+}
+
+// The hash is the 32-byte output for "password", "saltsaltsalt" and 8 rounds
+// from the `bcrypt-pbkdf` crate on crates.io, encoded with Python's `base64`.
+#[cfg(feature="phc")]
+#[test]
+fn test_verify_phc() {
+    use bcrypt_pbkdf::verify_phc;
+
+    let phc = "$bcrypt-pbkdf$r=8$c2FsdHNhbHRzYWx0$DGYa1LdACKKaODGHf0dTo2tdKewkl73LjTSdjPKR6M4";
+    assert_eq!(verify_phc("password", phc), Ok(true));
+    assert_eq!(verify_phc("Password", phc), Ok(false));
+    assert_eq!(verify_phc("password", &phc.replace("r=8", "r=9")), Ok(false));
+
+    for malformed in [
+        "",
+        "bcrypt-pbkdf$r=8$c2FsdHNhbHRzYWx0$DGYa1LdACKKaODGHf0dTo2tdKewkl73LjTSdjPKR6M4",
+        "$pbkdf2-sha256$r=8$c2FsdHNhbHRzYWx0$DGYa1LdACKKaODGHf0dTo2tdKewkl73LjTSdjPKR6M4",
+        "$bcrypt-pbkdf$v=1$r=8$c2FsdHNhbHRzYWx0$DGYa1LdACKKaODGHf0dTo2tdKewkl73LjTSdjPKR6M4",
+        "$bcrypt-pbkdf$r=8,n=2$c2FsdHNhbHRzYWx0$DGYa1LdACKKaODGHf0dTo2tdKewkl73LjTSdjPKR6M4",
+        "$bcrypt-pbkdf$r=x$c2FsdHNhbHRzYWx0$DGYa1LdACKKaODGHf0dTo2tdKewkl73LjTSdjPKR6M4",
+        "$bcrypt-pbkdf$c2FsdHNhbHRzYWx0$DGYa1LdACKKaODGHf0dTo2tdKewkl73LjTSdjPKR6M4",
+        "$bcrypt-pbkdf$r=8$c2FsdHNhbHRzYWx0",
+        "$bcrypt-pbkdf$r=8$c2FsdHNhbHRzYWx0$DGYa1LdACKKa!DGHf0dTo2tdKewkl73LjTSdjPKR6M4",
+    ].iter() {
+        assert_eq!(verify_phc("password", malformed), Err(Error::InvalidFormat), "{}", malformed);
+    }
+}
+
+#[cfg(feature="zeroize")]
+#[test]
+fn test_take_string() {
+    for t in tests().iter().take(3) {
+        let mut out = vec![0u8; t.out.len()];
+        let passphrase = t.password.to_string();
+        bcrypt_pbkdf::bcrypt_pbkdf_take_string(passphrase, &t.salt, t.rounds, &mut out);
+        assert_eq!(out, t.out);
+    }
+}
+
+// The expected outputs were produced by this implementation when the shim
+// was added, and freeze its behaviour. Only the blocks it shares with the
+// standard function have an external reference.
+#[cfg(feature="legacy")]
+#[test]
+fn test_legacy_counter() {
+    use bcrypt_pbkdf::bcrypt_pbkdf_legacy_counter;
+
+    let mut out = [0u8; 32];
+    bcrypt_pbkdf_legacy_counter("password", b"salt", 4, &mut out);
+    assert_eq!(out[..], decode_hex(
+        "6fa67f7567304ec4d0581b3ee69c2fabba9c545514f18f7edaef18c8abaafc17")[..]);
+
+    let mut out = [0u8; 64];
+    bcrypt_pbkdf_legacy_counter("password", b"salt", 4, &mut out);
+    assert_eq!(out[..], decode_hex(
+        "6f5ba6bf7f0c75c2679330584e7fc41cd03658351b553e5ce6279c792f65ab98\
+         bad49c7e545755901471f1bf8f427e7eda9def8f18bec884ab2aaabafc3417d9")[..]);
+
+    // Block 1 (counter 1) is the first block of the standard function, so the
+    // odd bytes are the 32-byte OpenBSD test vector.
+    let mut standard = [0u8; 32];
+    bcrypt_pbkdf("password", b"salt", 4, &mut standard);
+    assert_eq!(standard[..], decode_hex(
+        "5bbf0cc293587f1c3635555c27796598d47e579071bf427e9d8fbe842aba34d9")[..]);
+    let odd: Vec<u8> = out.iter().skip(1).step_by(2).cloned().collect();
+    assert_eq!(odd[..], standard[..]);
+    assert_ne!(out[..32], standard[..]);
+}
+
+#[test]
+#[should_panic(expected = "rounds must not be zero")]
+fn test_zero_rounds_rejected() {
+    bcrypt_pbkdf("password", b"salt", 0, &mut [0; 32]);
+}
+
+#[test]
+#[should_panic(expected = "rounds must not be zero")]
+fn test_zero_rounds_rejected_prepared() {
+    PreparedPassphrase::new("password").derive(b"salt", 0, &mut [0; 32]);
+}
+
+#[test]
+fn test_zero_rounds_rejected_fallible() {
+    use std::sync::atomic::AtomicBool;
+
+    let mut output = [0u8; 32];
+    assert_eq!(BcryptPbkdf::new(0).derive(b"password", b"salt", &mut output),
+        Err(Error::InvalidRounds));
+    assert_eq!(Params::new(0, 32).derive("password", b"salt"), Err(Error::InvalidRounds));
+    assert_eq!(bcrypt_pbkdf_cancellable("password", b"salt", 0, &mut output,
+        &AtomicBool::new(false)), Err(Error::InvalidRounds));
+    assert_eq!(bcrypt_pbkdf_exact::<32>("password", b"salt", 0, &mut output),
+        Err(Error::InvalidRounds));
+    assert_eq!(bcrypt_pbkdf_redundant("password", b"salt", 0, &mut output),
+        Err(Error::InvalidRounds));
+    assert_eq!(bcrypt_pbkdf_in_place("password", b"salt", 0, &mut output, &mut [0u8; 32]),
+        Err(Error::InvalidRounds));
+    assert_eq!(verify_with_scratch("password", b"salt", 0, &output, &mut [0u8; 32]),
+        Err(Error::InvalidRounds));
+
+    // The rounds are checked before the salt is fetched.
+    struct Unavailable;
+    impl SaltSource for Unavailable {
+        fn salt(&self) -> Result<Vec<u8>, Error> {
+            Err(Error::SaltUnavailable)
+        }
+    }
+    assert_eq!(derive_with_salt_source("password", &Unavailable, 0, &mut output),
+        Err(Error::InvalidRounds));
+    assert_eq!(output, [0u8; 32]);
+}
+
+// Zero rounds give the output of one round, which is from the `bcrypt-pbkdf`
+// crate from crates.io (it rejects zero rounds itself).
+#[cfg(feature="legacy")]
+#[test]
+fn test_legacy_zero_rounds() {
+    use bcrypt_pbkdf::bcrypt_pbkdf_allow_zero_rounds;
+
+    let mut out = [0u8; 32];
+    bcrypt_pbkdf_allow_zero_rounds("password", b"salt", &mut out);
+    assert_eq!(out[..], decode_hex(
+        "7af41469e52860da49debfaa6a4e12a2cb857b907b2bb20d2902a9ccd424adb1")[..]);
+
+    let mut one_round = [0u8; 64];
+    bcrypt_pbkdf("password", b"salt", 1, &mut one_round);
+    let mut out = [0u8; 64];
+    bcrypt_pbkdf_allow_zero_rounds("password", b"salt", &mut out);
+    assert_eq!(out[..], one_round[..]);
+}
+
+#[cfg(feature="legacy")]
+#[test]
+fn test_legacy_reversed() {
+    use bcrypt_pbkdf::bcrypt_pbkdf_reversed;
+
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        bcrypt_pbkdf_reversed(t.password, &t.salt, t.rounds, &mut out);
+        let expected: Vec<u8> = t.out.iter().rev().cloned().collect();
+        assert_eq!(out, expected);
+    }
+}
+
+#[cfg(feature="legacy")]
+#[test]
+fn test_looks_like_bcrypt_pbkdf() {
+    use bcrypt_pbkdf::{looks_like_bcrypt_pbkdf, Classification};
+
+    let t = &tests()[0];
+    assert_eq!(t.out.len(), 32);
+    assert_eq!(looks_like_bcrypt_pbkdf(t.password, &t.salt, t.rounds, &t.out),
+        Classification::Ambiguous);
+
+    for t in tests().iter().filter(|t| t.out.len() > 32) {
+        assert_eq!(looks_like_bcrypt_pbkdf(t.password, &t.salt, t.rounds, &t.out),
+            Classification::BcryptPbkdf);
+
+        // Undo the transpose to get what a port without it would output. For
+        // whole blocks every PBKDF2 byte is used, so this gives all of them.
+        if t.out.len() % 32 == 0 {
+            let stride = t.out.len() / 32;
+            let mut untransposed = vec![0u8; t.out.len()];
+            for (i, &b) in t.out.iter().enumerate() {
+                untransposed[(i % stride) * 32 + i / stride] = b;
+            }
+            assert_eq!(looks_like_bcrypt_pbkdf(t.password, &t.salt, t.rounds, &untransposed),
+                Classification::Untransposed);
+        }
+
+        let mut wrong = t.out.clone();
+        wrong[0] ^= 1;
+        assert_eq!(looks_like_bcrypt_pbkdf(t.password, &t.salt, t.rounds, &wrong),
+            Classification::NoMatch);
+    }
+}
+
+#[test]
+fn test_info() {
+    let info = bcrypt_pbkdf::info();
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.backend, "scalar");
+    assert_eq!(info.zeroize, cfg!(feature="zeroize"));
+    assert_eq!(info.max_output_len, 1024);
+    assert_eq!(info.max_output_len, bcrypt_pbkdf::MAX_OUTPUT_LEN);
+}
+
+// Ignored by default because it depends on timing; run it with
+// `cargo test -- --ignored`.
+#[test]
+#[ignore]
+fn test_time_rounds() {
+    let rounds_list = [4, 16, 1, 64];
+    let times = time_rounds("password", b"salt", 32, &rounds_list);
+    let rounds: Vec<u32> = times.iter().map(|&(rounds, _)| rounds).collect();
+    assert_eq!(rounds, rounds_list);
+
+    // The cost is linear in the number of rounds, so a 16 times larger count
+    // takes clearly longer even on a noisy machine.
+    let time = |rounds| times.iter().find(|t| t.0 == rounds).unwrap().1;
+    assert!(time(64) > time(4), "{:?}", times);
+    assert!(time(16) > time(1), "{:?}", times);
+
+    assert!(time_rounds("password", b"salt", 32, &[]).is_empty());
+}
+
+/// A deterministic `RngCore`, returning consecutive bytes from `self.0`.
+#[cfg(feature="rand_core")]
+struct CountingRng(u8);
+
+#[cfg(feature="rand_core")]
+impl rand_core::RngCore for CountingRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            *byte = self.0;
+            self.0 = self.0.wrapping_add(1);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(feature="rand_core")]
+#[test]
+fn test_credential() {
+    use bcrypt_pbkdf::{Credential, CREDENTIAL_LEN, CREDENTIAL_VERSION};
+
+    let mut rng = CountingRng(0);
+    let credential = Credential::create(&mut rng, "password", 4);
+    assert_eq!(credential.version, CREDENTIAL_VERSION);
+    assert_eq!(credential.rounds, 4);
+    let salt: [u8; 16] = std::array::from_fn(|i| i as u8);
+    assert_eq!(credential.salt, salt);
+    let mut hash = [0u8; 32];
+    bcrypt_pbkdf("password", &salt, 4, &mut hash);
+    assert_eq!(credential.hash, hash);
+
+    assert!(credential.verify("password"));
+    assert!(!credential.verify("passwore"));
+    assert!(!credential.verify(""));
+
+    // A second credential gets a different salt, so a different hash.
+    let other = Credential::create(&mut rng, "password", 4);
+    assert_ne!(other.salt, credential.salt);
+    assert_ne!(other.hash, credential.hash);
+    assert!(other.verify("password"));
+
+    let bytes = credential.to_bytes();
+    assert_eq!(bytes.len(), CREDENTIAL_LEN);
+    assert_eq!(bytes[..5], [CREDENTIAL_VERSION, 0, 0, 0, 4]);
+    assert_eq!(bytes[5..21], salt);
+    assert_eq!(bytes[21..], hash);
+    let parsed = Credential::from_bytes(&bytes).unwrap();
+    assert_eq!(parsed, credential);
+    assert!(parsed.verify("password"));
+
+    assert_eq!(Credential::from_bytes(&bytes[..CREDENTIAL_LEN - 1]), Err(Error::InvalidFormat));
+    let mut bad = bytes;
+    bad[0] = CREDENTIAL_VERSION + 1;
+    assert_eq!(Credential::from_bytes(&bad), Err(Error::InvalidFormat));
+    let mut bad = bytes;
+    bad[1..5].copy_from_slice(&[0; 4]);
+    assert_eq!(Credential::from_bytes(&bad), Err(Error::InvalidRounds));
+
+    let unknown = Credential { version: CREDENTIAL_VERSION + 1, ..credential };
+    assert!(!unknown.verify("password"));
+}
+
+#[cfg(feature="nonstandard")]
+#[test]
+fn test_seeded() {
+    use bcrypt_pbkdf::bcrypt_pbkdf_seeded;
+
+    for t in tests().iter().take(3) {
+        let mut out = vec![0u8; t.out.len()];
+        bcrypt_pbkdf_seeded(t.password, &t.salt, t.rounds, &mut out,
+            b"OxychromaticBlowfishSwatDynamite");
+        assert_eq!(out, t.out);
+
+        bcrypt_pbkdf_seeded(t.password, &t.salt, t.rounds, &mut out,
+            b"example.com key derivation v1\0\0\0");
+        assert_ne!(out, t.out);
+    }
+}
+
+#[cfg(feature="nonstandard")]
+#[test]
+fn test_bhash_custom() {
+    use bcrypt_pbkdf::bhash_custom;
+    use sha2::Digest;
+
+    // A one-round, one-block derivation is a single bcrypt hash of the salt
+    // followed by the block counter.
+    for t in tests().iter().take(4) {
+        let mut expected = [0u8; 32];
+        bcrypt_pbkdf(t.password, &t.salt, 1, &mut expected);
+        let sha2_pass = Sha512::digest(t.password.as_bytes());
+        let sha2_salt = Sha512::new().chain(&t.salt).chain([0, 0, 0, 1]).result();
+        assert_eq!(bhash_custom(&sha2_pass, &sha2_salt, 64, 64), expected);
+
+        assert_ne!(bhash_custom(&sha2_pass, &sha2_salt, 63, 64), expected);
+        assert_ne!(bhash_custom(&sha2_pass, &sha2_salt, 64, 1), expected);
+        assert_ne!(bhash_custom(&sha2_pass, &sha2_salt, 0, 0), expected);
+    }
+}
+
+#[cfg(all(feature="nonstandard", feature="internals"))]
+#[test]
+fn test_bhash_custom_internals() {
+    use bcrypt_pbkdf::bhash_custom;
+    use bcrypt_pbkdf::internals::bhash;
+    use sha2::Digest;
+
+    let sha2_pass = Sha512::digest(b"password");
+    let sha2_salt = Sha512::digest(b"salt");
+    assert_eq!(bhash_custom(&sha2_pass, &sha2_salt, 64, 64), bhash(&sha2_pass, &sha2_salt));
+}
+
+#[test]
+fn test_grid_pool() {
+    let pool = GridPool::new();
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        pool.derive(t.password, &t.salt, t.rounds, &mut out);
+        assert_eq!(out, t.out);
+    }
+    assert_eq!(pool.idle(), 1);
+}
+
+#[test]
+fn test_grid_pool_threads() {
+    let pool = Arc::new(GridPool::new());
+    let handles: Vec<_> = (0..4).map(|_| {
+        let pool = pool.clone();
+        thread::spawn(move || {
+            for t in tests().iter() {
+                let mut out = vec![0u8; t.out.len()];
+                pool.derive(t.password, &t.salt, t.rounds, &mut out);
+                assert_eq!(out, t.out);
+            }
+        })
+    }).collect();
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert!(pool.idle() >= 1 && pool.idle() <= 4);
+}
+
+#[test]
+fn test_redundant() {
+    for t in tests().iter() {
+        let mut out = vec![0u8; t.out.len()];
+        assert_eq!(bcrypt_pbkdf_redundant(t.password, &t.salt, t.rounds, &mut out), Ok(()));
+        assert_eq!(out, t.out);
+    }
+}
+
+#[cfg(feature="internals")]
+#[test]
+fn test_redundant_fault() {
+    let t = &tests()[0];
+    let mut out = vec![0u8; t.out.len()];
+    let res = bcrypt_pbkdf::internals::bcrypt_pbkdf_redundant_faulty(
+        t.password, &t.salt, t.rounds, &mut out);
+    assert_eq!(res, Err(Error::ComputationMismatch));
+    assert!(out.iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_emit() {
+    for t in tests().iter() {
+        for &chunk in [1, 7, 1000].iter() {
+            let mut out = Vec::new();
+            let mut pieces = 0;
+            bcrypt_pbkdf_emit(t.password, &t.salt, t.rounds, t.out.len(), chunk, |piece| {
+                assert!(piece.len() <= chunk);
+                out.extend_from_slice(piece);
+                pieces += 1;
+            });
+            assert_eq!(out, t.out);
+            assert_eq!(pieces, t.out.len().div_ceil(chunk));
+        }
+    }
+}
+
+#[test]
+fn test_checkpoint() {
+    for t in tests().iter() {
+        // Save and restore the state after every block.
+        let mut checkpoint = DerivationCheckpoint::new(&t.salt, t.rounds, t.out.len());
+        let total = checkpoint.total_blocks();
+        assert_eq!(total, t.out.len().div_ceil(32));
+        let mut out = None;
+        for done in 1..=total {
+            out = checkpoint.resume(t.password, 1);
+            assert_eq!(checkpoint.completed_blocks(), done);
+            assert_eq!(out.is_some(), done == total);
+            checkpoint = DerivationCheckpoint::from_bytes(&checkpoint.to_bytes()).unwrap();
+        }
+        assert_eq!(out.unwrap(), t.out);
+        assert_eq!(checkpoint.resume(t.password, 1).unwrap(), t.out);
+    }
+
+    let t = &tests()[6];
+    let mut checkpoint = DerivationCheckpoint::new(&t.salt, t.rounds, t.out.len());
+    assert_eq!(checkpoint.resume(t.password, usize::MAX).unwrap(), t.out);
+}
+
+#[test]
+fn test_checkpoint_from_bytes_errors() {
+    let t = &tests()[6];
+    let mut checkpoint = DerivationCheckpoint::new(&t.salt, t.rounds, 64);
+    assert!(checkpoint.resume(t.password, 1).is_none());
+    let bytes = checkpoint.to_bytes();
+    assert_eq!(bytes.len(), 20 + t.salt.len() + 32);
+    assert!(DerivationCheckpoint::from_bytes(&bytes).is_ok());
+
+    // Truncated header, salt or block.
+    for len in [0, 19, 20, 20 + t.salt.len() + 31].iter() {
+        assert!(DerivationCheckpoint::from_bytes(&bytes[..*len]).is_err(), "len {}", len);
+    }
+    // More blocks than the output needs.
+    let mut extra = bytes.clone();
+    extra.extend_from_slice(&[0u8; 64]);
+    assert!(DerivationCheckpoint::from_bytes(&extra).is_err());
+    // A salt longer than the remaining input.
+    let mut long_salt = bytes.clone();
+    long_salt[12..20].copy_from_slice(&u64::MAX.to_be_bytes());
+    assert!(DerivationCheckpoint::from_bytes(&long_salt).is_err());
+    // An output length which resuming could not allocate.
+    for &output_len in [MAX_OUTPUT_LEN as u64 + 1, usize::MAX as u64, u64::MAX].iter() {
+        let mut huge = bytes.clone();
+        huge[4..12].copy_from_slice(&output_len.to_be_bytes());
+        assert_eq!(DerivationCheckpoint::from_bytes(&huge).err(), Some(Error::InvalidOutputLen));
+    }
+}
+
+#[test]
+fn test_fixed() {
+    let salt = *b"0123456789abcdef";
+    let out: [u8; 32] = bcrypt_pbkdf_fixed(b"fixed", &salt, 16).unwrap();
+    let mut expected = [0u8; 32];
+    bcrypt_pbkdf("fixed", &salt, 16, &mut expected);
+    assert_eq!(out, expected);
+
+    // 48 bytes is not a whole number of blocks.
+    let out: [u8; 48] = bcrypt_pbkdf_fixed(b"fixed", &salt, 16).unwrap();
+    let mut expected = [0u8; 48];
+    bcrypt_pbkdf("fixed", &salt, 16, &mut expected);
+    assert_eq!(out[..], expected[..]);
+
+    assert_eq!(bcrypt_pbkdf_fixed::<32>(b"fixed", &salt, 0), Err(Error::InvalidRounds));
+}
+
+#[cfg(feature="heapless")]
+#[test]
+fn test_heapless() {
+    for t in tests().iter().filter(|t| t.out.len() == 32) {
+        let out = bcrypt_pbkdf::bcrypt_pbkdf_heapless::<32>(
+            t.password.as_bytes(), &t.salt, t.rounds);
+        assert_eq!(out.len(), 32);
+        assert_eq!(out[..], t.out[..]);
+    }
+
+    let t = &tests()[10];
+    let out = bcrypt_pbkdf::bcrypt_pbkdf_heapless::<47>(t.password.as_bytes(), &t.salt, t.rounds);
+    assert_eq!(out[..], t.out[..]);
+}
+
+#[test]
+fn test_params() {
+    for t in tests().iter() {
+        let out = Params::new(t.rounds, t.out.len()).derive(t.password, &t.salt).unwrap();
+        assert_eq!(out, t.out);
+    }
+}
+
+#[test]
+fn test_salted_params() {
+    for t in tests().iter() {
+        let params = SaltedParams::new(&t.salt, t.rounds, t.out.len()).unwrap();
+        assert_eq!((params.salt(), params.rounds(), params.output_len()),
+            (&t.salt[..], t.rounds, t.out.len()));
+        assert_eq!(params.derive(t.password), t.out);
+    }
+
+    let salt = vec![0x5a; DEFAULT_MAX_SALT_LEN + 1];
+    assert!(SaltedParams::new(&salt[..DEFAULT_MAX_SALT_LEN], 1, 32).is_ok());
+    assert_eq!(SaltedParams::new(&salt, 1, 32), Err(Error::SaltTooLong));
+    assert_eq!(SaltedParams::new(b"salt", 0, 32), Err(Error::InvalidRounds));
+    assert_eq!(SaltedParams::new(b"salt", 4, 0), Err(Error::InvalidOutputLen));
+    assert!(SaltedParams::new(b"salt", 4, MAX_OUTPUT_LEN).is_ok());
+    assert_eq!(SaltedParams::new(b"salt", 4, MAX_OUTPUT_LEN + 1), Err(Error::InvalidOutputLen));
+}
+
+#[cfg(feature="serde")]
+#[test]
+fn test_salted_params_serde() {
+    let params = SaltedParams::new(b"salt", 4, 32).unwrap();
+    let json = serde_json::to_string(&params).unwrap();
+    assert_eq!(json, r#"{"salt":[115,97,108,116],"rounds":4,"output_len":32}"#);
+    let parsed: SaltedParams = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, params);
+    assert_eq!(parsed.derive("password"), tests()[0].out);
+
+    // Deserialization validates the parameters.
+    let err = serde_json::from_str::<SaltedParams>(
+        r#"{"salt":[115,97,108,116],"rounds":0,"output_len":32}"#).unwrap_err();
+    assert!(err.to_string().contains("invalid number of rounds"), "{}", err);
+    assert!(serde_json::from_str::<SaltedParams>(
+        r#"{"salt":[115],"rounds":4,"output_len":0}"#).is_err());
+    assert!(serde_json::from_str::<SaltedParams>(r#"{"salt":[115],"rounds":4}"#).is_err());
+}
+
+#[test]
+fn test_params_cache_key() {
+    use std::collections::HashMap;
+
+    let mut cache: HashMap<Params, Vec<u8>> = HashMap::new();
+    for t in tests().iter().take(2) {
+        let params = Params::new(t.rounds, t.out.len());
+        cache.insert(params, params.derive(t.password, &t.salt).unwrap());
+    }
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache[&Params::new(4, 32)], tests()[0].out);
+    assert!(!cache.contains_key(&Params::new(4, 32).max_salt_len(16)));
+}
+
+#[test]
+fn test_params_reject_zero_salt() {
+    let params = Params::new(4, 32);
+    assert!(params.derive("password", &[0u8; 16]).is_ok());
+
+    let params = params.reject_zero_salt(true);
+    assert_eq!(params.derive("password", &[0u8; 16]), Err(Error::AllZeroSalt));
+    assert_eq!(params.derive("password", &[0u8]), Err(Error::AllZeroSalt));
+    assert!(params.derive("password", &[0, 0, 1]).is_ok());
+}
+
+#[test]
+fn test_params_reject_empty_passphrase() {
+    let params = Params::new(4, 32);
+    assert!(params.derive("", b"salt").is_ok());
+
+    let params = params.reject_empty_passphrase(true);
+    assert_eq!(params.derive("", b"salt"), Err(Error::EmptyPassphrase));
+    assert_eq!(params.derive("password", b"salt").unwrap(), tests()[0].out);
+}
+
+#[test]
+fn test_params_salt_too_long() {
+    let params = Params::new(1, 32);
+    let salt = vec![0x5a; DEFAULT_MAX_SALT_LEN + 1];
+    assert!(params.derive("password", &salt[..DEFAULT_MAX_SALT_LEN]).is_ok());
+    assert_eq!(params.derive("password", &salt), Err(Error::SaltTooLong));
+
+    let params = params.max_salt_len(16);
+    assert!(params.derive("password", &salt[..16]).is_ok());
+    assert_eq!(params.derive("password", &salt[..17]), Err(Error::SaltTooLong));
+
+    // The unvalidated function still accepts the long salt.
+    let mut out = [0u8; 32];
+    bcrypt_pbkdf("password", &salt, 1, &mut out);
+    assert_ne!(out, [0u8; 32]);
+}
+
+#[test]
+fn test_params_passphrase_too_long() {
+    let params = Params::new(1, 32);
+    let passphrase = "x".repeat(DEFAULT_MAX_PASSPHRASE_LEN + 1);
+    assert!(params.derive(&passphrase[..DEFAULT_MAX_PASSPHRASE_LEN], b"salt").is_ok());
+    assert_eq!(params.derive(&passphrase, b"salt"), Err(Error::PassphraseTooLong));
+
+    // The longest OpenBSD vector's passphrase is well within the default.
+    let t = &tests()[8];
+    assert!(t.password.len() > 400);
+    let params = Params::new(t.rounds, t.out.len());
+    assert_eq!(params.derive(t.password, &t.salt).unwrap(), t.out);
+
+    let params = params.max_passphrase_len(t.password.len());
+    assert_eq!(params.derive(t.password, &t.salt).unwrap(), t.out);
+    let params = params.max_passphrase_len(t.password.len() - 1);
+    assert_eq!(params.derive(t.password, &t.salt), Err(Error::PassphraseTooLong));
+}
+
+// HMAC-SHA256 tags computed with Python's `hmac` module over the outputs of
+// the first two OpenBSD vectors.
+#[cfg(feature="hmac")]
+#[test]
+fn test_tagged() {
+    let t = &tests()[0];
+    let (key, tag) = bcrypt_pbkdf::bcrypt_pbkdf_tagged(
+        t.password, &t.salt, t.rounds, 32, b"storage mac key");
+    assert_eq!(key, t.out);
+    assert_eq!(tag[..], decode_hex(
+        "d8835184bf74b10abbe29415145cf47e680e50a3a72971fc9444486f1f29917b")[..]);
+
+    let t = &tests()[1];
+    let (key, tag) = bcrypt_pbkdf::bcrypt_pbkdf_tagged(t.password, &t.salt, t.rounds, 16, b"");
+    assert_eq!(key, t.out);
+    assert_eq!(tag[..], decode_hex(
+        "1d43b6bc999e10a0fea2a6e8afec707db5f45ac7e2d43bc11ca2bf31d11e9019")[..]);
+}
+
+// HKDF-Expand (SHA-256) outputs computed with Python's `cryptography`
+// package, using the first OpenBSD vector's output as the PRK.
+#[cfg(feature="hkdf")]
+#[test]
+fn test_then_hkdf() {
+    let okm = bcrypt_pbkdf::bcrypt_pbkdf_then_hkdf("password", b"salt", 4, b"", 42);
+    assert_eq!(okm, decode_hex(
+        "ddee72cc0154eb5611f3238276f1953ebb897d1982d06ce775b9f130a765b82ff365c7f8d1e88c5e9dc3"));
+
+    let okm = bcrypt_pbkdf::bcrypt_pbkdf_then_hkdf("password", b"salt", 4, b"ssh-key", 100);
+    assert_eq!(okm, decode_hex(
+        "aadfeb43225845e6837a0975ee03ed78be51fe4754a289aabea078e046dda93e\
+         7c77bae70a7732cf900553a30aeddf0a4ae8bedc2266061b7f3eab97f54f4f1d\
+         73e0b1bb033e46ef5c55db06725855b5517c47531d50e12301623583981be73d\
+         19c1a8c7"));
+}
+
+// `data/id_ed25519_aes256-ctr` was generated with
+// `ssh-keygen -t ed25519 -a 16 -Z aes256-ctr -N "correct horse"`, and
+// `data/id_ed25519_none` with an empty passphrase.
+#[cfg(feature="openssh")]
+#[test]
+fn test_parse_openssh_kdf() {
+    let parsed = bcrypt_pbkdf::parse_openssh_kdf(
+        include_str!("data/id_ed25519_aes256-ctr")).unwrap();
+    assert_eq!(parsed.cipher_name, "aes256-ctr");
+    assert_eq!(parsed.kdf_name, "bcrypt");
+    assert_eq!(parsed.kdf_options.salt, decode_hex("3f74c0a987ce12ac80defb29ce5a085d"));
+    assert_eq!(parsed.kdf_options.rounds, 16);
+
+    // The key and IV which decrypt the private key.
+    let mut out = [0u8; 48];
+    bcrypt_pbkdf("correct horse", &parsed.kdf_options.salt, parsed.kdf_options.rounds, &mut out);
+    assert_eq!(out[..], decode_hex(
+        "4092ab52a5b9d7d848a634b9a242544a3de427f91402240411c32f2df40cd840\
+         1d80f768f89a631ad5002400f5b13405")[..]);
+}
+
+#[cfg(feature="openssh")]
+#[test]
+fn test_parse_openssh_kdf_errors() {
+    let parse = bcrypt_pbkdf::parse_openssh_kdf;
+    assert_eq!(parse(include_str!("data/id_ed25519_none")), Err(Error::UnsupportedKdf));
+    assert_eq!(parse("not a key"), Err(Error::InvalidFormat));
+
+    // Truncated inside the kdfoptions.
+    let pem = include_str!("data/id_ed25519_aes256-ctr");
+    let lines: Vec<&str> = pem.lines().collect();
+    let truncated = format!("{}\n{}\n{}\n", lines[0], &lines[1][..60], lines[lines.len() - 1]);
+    assert_eq!(parse(&truncated), Err(Error::InvalidFormat));
+}
+
+#[cfg(feature="openssh")]
+#[test]
+fn test_encode_kdfoptions() {
+    use bcrypt_pbkdf::{encode_kdfoptions, KdfOptions};
+
+    assert_eq!(encode_kdfoptions(b"salt", 16), b"\0\0\0\x04salt\0\0\0\x10");
+    for &(salt, rounds) in [
+        (&b""[..], 1),
+        (&b"salt"[..], 16),
+        (&[0xa5; 16][..], u32::MAX),
+        (&[0x5a; 300][..], 0x0102_0304),
+    ].iter() {
+        let options = KdfOptions::parse(&encode_kdfoptions(salt, rounds)).unwrap();
+        assert_eq!(options, KdfOptions { salt: salt.to_vec(), rounds });
+    }
+    assert_eq!(KdfOptions::parse(&encode_kdfoptions(b"salt", 0)), Err(Error::InvalidRounds));
+}
+
+// `data/id_ecdsa_aes256-ctr` was generated with
+// `ssh-keygen -t ecdsa -b 256 -N "correct horse" -a 4`.
+#[cfg(feature="openssh")]
+#[test]
+fn test_derive_for_key_type() {
+    use bcrypt_pbkdf::{
+        derive_for_key_type, derive_openssh_chacha, encode_kdfoptions, parse_openssh_kdf,
+        SshCipher, SshKeyType,
+    };
+
+    let parsed = parse_openssh_kdf(include_str!("data/id_ecdsa_aes256-ctr")).unwrap();
+    let key_type = SshKeyType::EcdsaP256(SshCipher::Aes256Ctr);
+    assert_eq!(parsed.cipher_name, key_type.cipher_name());
+    let kdfoptions = encode_kdfoptions(&parsed.kdf_options.salt, parsed.kdf_options.rounds);
+    let key_iv = derive_for_key_type("correct horse", &kdfoptions, key_type).unwrap();
+    assert_eq!(key_iv, decode_hex(
+        "29e7038561fdb9cec63bcadb720ace85f25405cb9b999b104baeffc4cba5bb63\
+         ea15e35100c2cc7b657469bf5c00b756"));
+
+    // The output length follows the cipher, whatever the key type.
+    for &(file, len) in &[
+        (include_str!("data/id_ed25519_aes256-ctr"), 32 + 16),
+        (include_str!("data/id_ed25519_aes128-cbc"), 16 + 16),
+        (include_str!("data/id_ed25519_aes256-gcm"), 32 + 12),
+        (include_str!("data/id_ed25519_chacha20-poly1305"), 64),
+    ] {
+        let parsed = parse_openssh_kdf(file).unwrap();
+        let cipher = SshCipher::from_name(&parsed.cipher_name).unwrap();
+        assert_eq!(cipher.name(), parsed.cipher_name);
+        let key_type = SshKeyType::Ed25519(cipher);
+        assert_eq!(key_type.output_len(), len, "{}", parsed.cipher_name);
+        let kdfoptions =
+            encode_kdfoptions(&parsed.kdf_options.salt, parsed.kdf_options.rounds);
+        let key_iv = derive_for_key_type("correct horse", &kdfoptions, key_type).unwrap();
+        let mut expected = vec![0u8; len];
+        bcrypt_pbkdf("correct horse", &parsed.kdf_options.salt, parsed.kdf_options.rounds,
+            &mut expected);
+        assert_eq!(key_iv, expected, "{}", parsed.cipher_name);
+        if cipher == SshCipher::Chacha20Poly1305 {
+            let (main_key, header_key) =
+                derive_openssh_chacha("correct horse", &kdfoptions).unwrap();
+            assert_eq!((&key_iv[..32], &key_iv[32..]), (&main_key[..], &header_key[..]));
+        }
+    }
+
+    assert_eq!(SshKeyType::Rsa(SshCipher::Aes192Ctr).output_len(), 24 + 16);
+    assert_eq!(SshCipher::from_name("none"), None);
+    let key_type = SshKeyType::Rsa(SshCipher::Aes256Ctr);
+    assert_eq!(derive_for_key_type("correct horse", &kdfoptions[1..], key_type),
+        Err(Error::InvalidFormat));
+    assert_eq!(derive_for_key_type("correct horse", &encode_kdfoptions(b"salt", 0), key_type),
+        Err(Error::InvalidRounds));
+}
+
+// `data/id_ed25519_chacha20-poly1305` was generated with `ssh-keygen -t
+// ed25519 -a 4 -Z chacha20-poly1305@openssh.com -N "correct horse"`. The
+// first key was checked by decrypting the check integers with Python's
+// `cryptography`.
+#[cfg(feature="openssh")]
+#[test]
+fn test_derive_openssh_chacha() {
+    use bcrypt_pbkdf::{derive_openssh_chacha, encode_kdfoptions, parse_openssh_kdf};
+
+    let parsed = parse_openssh_kdf(include_str!("data/id_ed25519_chacha20-poly1305")).unwrap();
+    assert_eq!(parsed.cipher_name, "chacha20-poly1305@openssh.com");
+    let kdfoptions = encode_kdfoptions(&parsed.kdf_options.salt, parsed.kdf_options.rounds);
+    let (main_key, header_key) = derive_openssh_chacha("correct horse", &kdfoptions).unwrap();
+    assert_eq!(main_key[..], decode_hex(
+        "0ac4a4bba8a5937104174882ff58459446fb25a6608da4c1b8ccf2227ff0152f")[..]);
+    assert_eq!(header_key[..], decode_hex(
+        "2b072ba0fc7351ac0a8f79edeeee47b2ef648ef76fb8998ad944ad90df8bb3ce")[..]);
+
+    assert_eq!(derive_openssh_chacha("correct horse", &kdfoptions[1..]),
+        Err(Error::InvalidFormat));
+}
+
+// `data/id_ed25519_aes128-cbc` and `data/id_ed25519_aes256-gcm` were
+// generated like `data/id_ed25519_aes256-ctr`, with `-a 8`.
+#[cfg(feature="cipher")]
+#[test]
+fn test_openssh_passphrase_correct() {
+    use bcrypt_pbkdf::{openssh_passphrase_correct, parse_openssh_kdf};
+
+    for pem in [
+        include_str!("data/id_ed25519_aes256-ctr"),
+        include_str!("data/id_ed25519_aes128-cbc"),
+        include_str!("data/id_ed25519_aes256-gcm"),
+    ].iter() {
+        let parsed = parse_openssh_kdf(pem).unwrap();
+        let check = |passphrase| openssh_passphrase_correct(
+            passphrase, &parsed.cipher_name, &parsed.kdf_options,
+            &parsed.encrypted_private_key);
+        assert_eq!(check("correct horse"), Ok(true), "{}", parsed.cipher_name);
+        assert_eq!(check("wrong horse"), Ok(false), "{}", parsed.cipher_name);
+    }
+
+    let parsed = parse_openssh_kdf(include_str!("data/id_ed25519_aes256-ctr")).unwrap();
+    assert_eq!(
+        openssh_passphrase_correct("correct horse", "3des-cbc", &parsed.kdf_options,
+            &parsed.encrypted_private_key),
+        Err(Error::UnsupportedCipher));
+    assert_eq!(
+        openssh_passphrase_correct("correct horse", "aes256-ctr", &parsed.kdf_options,
+            &parsed.encrypted_private_key[..8]),
+        Err(Error::InvalidFormat));
+}
+
+// The check integers were found by decrypting the first block of each key
+// with Python's `cryptography`.
+#[cfg(feature="cipher")]
+#[test]
+fn test_openssh_checkint_bytes() {
+    use bcrypt_pbkdf::{openssh_checkint_bytes, parse_openssh_kdf};
+
+    for &(pem, checkint) in [
+        (include_str!("data/id_ed25519_aes256-ctr"), 0x106b_6b29),
+        (include_str!("data/id_ed25519_aes128-cbc"), 0x6027_1801),
+        (include_str!("data/id_ecdsa_aes256-ctr"), 0xa2e4_a572),
+    ].iter() {
+        let parsed = parse_openssh_kdf(pem).unwrap();
+        // Only the first block is needed.
+        let head = &parsed.encrypted_private_key[..16];
+        let checkints = openssh_checkint_bytes(
+            "correct horse", &parsed.cipher_name, &parsed.kdf_options, head);
+        assert_eq!(checkints, Ok((checkint, checkint)), "{}", parsed.cipher_name);
+
+        let (check1, check2) = openssh_checkint_bytes(
+            "wrong horse", &parsed.cipher_name, &parsed.kdf_options, head).unwrap();
+        assert_ne!(check1, check2);
+    }
+}
+
+#[cfg(all(feature="ssh-key", feature="std"))]
+#[test]
+fn test_derive_ssh_key_kdf() {
+    use bcrypt_pbkdf::derive_ssh_key_kdf;
+    use ssh_key::{Cipher, Kdf, PrivateKey};
+
+    let key = PrivateKey::from_openssh(include_str!("data/id_ed25519_aes256-ctr")).unwrap();
+    let (k, iv) = derive_ssh_key_kdf("correct horse", key.kdf(), key.cipher()).unwrap();
+    assert_eq!((k.len(), iv.len()), (32, 16));
+
+    // Decrypt with ssh-key's own cipher: the check integers must match and be
+    // followed by the key type of the first private key.
+    let mut decrypted = key.key_data().encrypted().unwrap().to_vec();
+    key.cipher().decrypt(&k, &iv, &mut decrypted, None).unwrap();
+    assert_eq!(decrypted[..8], [0x10, 0x6b, 0x6b, 0x29, 0x10, 0x6b, 0x6b, 0x29]);
+    assert_eq!(decrypted[8..23], b"\0\0\0\x0bssh-ed25519"[..]);
+
+    assert_eq!(derive_ssh_key_kdf("correct horse", &Kdf::None, key.cipher()),
+        Err(Error::UnsupportedKdf));
+    assert_eq!(derive_ssh_key_kdf("correct horse", key.kdf(), Cipher::None),
+        Err(Error::UnsupportedCipher));
+}
+
+// The seeds were read from copies of the keys decrypted with
+// `ssh-keygen -p -P "correct horse" -N ""`.
+#[cfg(feature="cipher")]
+#[test]
+fn test_openssh_ed25519_seed() {
+    use bcrypt_pbkdf::{openssh_ed25519_seed, parse_openssh_kdf};
+
+    for &(pem, seed) in [
+        (include_str!("data/id_ed25519_aes256-ctr"),
+            "9ce4c7e7fc009ab16727bb7dd6e48af5abbfb40dbf417e8f3316c67614c7fbf2"),
+        (include_str!("data/id_ed25519_aes128-cbc"),
+            "fc4c1a9009528ed5648488a51e0ad04c733cd159afacdc8fd7940762df683bfe"),
+        (include_str!("data/id_ed25519_aes256-gcm"),
+            "7ab52f94d375f14f6aba381d5bf598e77a704a84ac8e66edb7b26b4cf470b5ae"),
+    ].iter() {
+        let parsed = parse_openssh_kdf(pem).unwrap();
+        assert_eq!(openssh_ed25519_seed("correct horse", &parsed).unwrap()[..],
+            decode_hex(seed)[..], "{}", parsed.cipher_name);
+        assert_eq!(openssh_ed25519_seed("wrong horse", &parsed),
+            Err(Error::IncorrectPassphrase), "{}", parsed.cipher_name);
+    }
+}
+
+/// `derive_block` must reproduce the PBKDF2 output computed by the `pbkdf2`
+/// crate with `Bhash` as the PRF, block for block.
+#[cfg(feature="internals")]
+#[test]
+fn test_derive_block() {
+    use bcrypt_pbkdf::internals::{derive_block, transpose_index, Bhash};
+    use sha2::Digest;
+
+    let sha2_pass = Sha512::digest(b"blocks");
+    let mut generated = [0u8; 96];
+    pbkdf2::pbkdf2::<Bhash>(&sha2_pass, b"salt", 5, &mut generated);
+
+    let mut assembled = Vec::new();
+    for j in 0..3 {
+        assembled.extend_from_slice(&derive_block(&sha2_pass, b"salt", 5, j));
+    }
+    assert_eq!(assembled[..], generated[..]);
+
+    let mut out = [0u8; 96];
+    bcrypt_pbkdf("blocks", b"salt", 5, &mut out);
+    for (i, &b) in out.iter().enumerate() {
+        assert_eq!(b, assembled[transpose_index(i, 3)]);
+    }
+}
+
+/// The PBKDF2 output must be the same as with the `pbkdf2` crate, which
+/// hashes the salt again for every block, including with a large salt.
+#[cfg(feature="internals")]
+#[test]
+fn test_salt_hashed_once() {
+    use bcrypt_pbkdf::internals::{transpose_index, Bhash};
+    use sha2::Digest;
+
+    let sha2_pass = Sha512::digest(b"password");
+    for salt in [b"salt".to_vec(), (0..100_000).map(|i| i as u8).collect()].iter() {
+        for &stride in [1usize, 2, 5].iter() {
+            let mut generated = vec![0u8; stride * 32];
+            pbkdf2::pbkdf2::<Bhash>(&sha2_pass, salt, 4, &mut generated);
+
+            let mut out = vec![0u8; stride * 32 - 7];
+            bcrypt_pbkdf("password", salt, 4, &mut out);
+            for (i, &b) in out.iter().enumerate() {
+                assert_eq!(b, generated[transpose_index(i, stride)]);
+            }
+        }
+    }
+}
+
+/// Outputs of up to 32 bytes take the stride-1 fast path, which must match
+/// the general transpose of the single PBKDF2 block.
+#[cfg(feature="internals")]
+#[test]
+fn test_stride_one() {
+    use bcrypt_pbkdf::internals::{derive_block, transpose_index};
+    use sha2::Digest;
+
+    let block = derive_block(&Sha512::digest(b"password"), b"salt", 4, 0);
+    for len in 1..=32 {
+        let mut out = vec![0u8; len];
+        bcrypt_pbkdf("password", b"salt", 4, &mut out);
+        let general: Vec<u8> = (0..len).map(|i| block[transpose_index(i, 1)]).collect();
+        assert_eq!(out, general, "len {}", len);
+
+        checked_bcrypt_pbkdf("password", b"salt", 4, &mut out).unwrap();
+        assert_eq!(out, general, "len {}", len);
+    }
+}
+
+/// The grid returned by `derive_with_grid` must be the one `output` was
+/// transposed from, and `output` must be the normal bcrypt_pbkdf output.
+#[cfg(feature="internals")]
+#[test]
+fn test_derive_with_grid() {
+    use bcrypt_pbkdf::internals::{derive_with_grid, transpose_index};
+
+    for &len in [1usize, 32, 47, 64, 100].iter() {
+        let stride = len.div_ceil(32);
+        let mut out = vec![0u8; len];
+        let grid = derive_with_grid("grid", b"salt", 4, &mut out);
+        assert_eq!(grid.len(), stride * 32);
+
+        let transposed: Vec<u8> = (0..len).map(|i| grid[transpose_index(i, stride)]).collect();
+        assert_eq!(transposed, out);
+
+        let mut expected = vec![0u8; len];
+        bcrypt_pbkdf("grid", b"salt", 4, &mut expected);
+        assert_eq!(out, expected);
+    }
+}
+
+/// With little-endian counters, every block of a multi-block output differs
+/// from the standard one. The first block of the legacy counter vectors is
+/// reproduced too.
+#[cfg(feature="internals")]
+#[test]
+fn test_derive_le_index() {
+    use bcrypt_pbkdf::internals::derive_le_index;
+
+    for &len in [64usize, 96, 128].iter() {
+        let mut le = vec![0u8; len];
+        derive_le_index("password", b"salt", 4, &mut le);
+        let mut standard = vec![0u8; len];
+        bcrypt_pbkdf("password", b"salt", 4, &mut standard);
+        let stride = len / 32;
+        for block in 0..stride {
+            let column = |out: &[u8]| -> Vec<u8> {
+                out.iter().skip(block).step_by(stride).cloned().collect()
+            };
+            assert_ne!(column(&le), column(&standard), "len {} block {}", len, block);
+        }
+    }
+
+    let mut out = [0u8; 32];
+    derive_le_index("password", b"salt", 4, &mut out);
+    assert_eq!(out[..], decode_hex(
+        "ce9c667b9054b90e766a60131186d5eeb4fab3626fd6233b04bb2bc48cc51f4b")[..]);
+}
+
+/// Every output length uses the same PBKDF2 blocks, only distributed
+/// differently, so recovering each block byte through `transpose_index` must
+/// give the same value whatever the output length.
+#[cfg(feature="internals")]
+#[test]
+fn test_transpose_consistency() {
+    use std::collections::HashMap;
+    use bcrypt_pbkdf::internals::transpose_index;
+
+    assert_eq!(transpose_index(0, 2), 0);
+    assert_eq!(transpose_index(1, 2), 32);
+    assert_eq!(transpose_index(2, 2), 1);
+    assert_eq!(transpose_index(64, 3), 53);
+
+    let mut blocks: HashMap<usize, u8> = HashMap::new();
+    let mut positions = Vec::new();
+    for &len in [32usize, 33, 64, 65, 96].iter() {
+        let stride = len.div_ceil(32);
+        let mut out = vec![0u8; len];
+        bcrypt_pbkdf("transpose", b"salt", 4, &mut out);
+        for (i, &b) in out.iter().enumerate() {
+            let pos = transpose_index(i, stride);
+            assert!(pos < stride * 32);
+            assert_eq!(*blocks.entry(pos).or_insert(b), b, "len {} byte {}", len, i);
+        }
+        positions.push(transpose_index(2, stride));
+    }
+    // All 96 bytes of the first three blocks were seen.
+    assert_eq!(blocks.len(), 96);
+    // Output byte 2 comes from a different block byte for each stride.
+    assert_eq!(positions, vec![2, 1, 1, 64, 64]);
+}
+
+/// An entry of `data/openssh.json`. Each entry is the key and IV which
+/// OpenSSH's `ssh-keygen` derived to encrypt a private key with `cipher`;
+/// they were confirmed by decrypting the key's check integers.
+#[derive(Deserialize)]
+struct OpenSshVector {
+    passphrase: String,
+    salt_hex: String,
+    rounds: u32,
+    out_len: usize,
+    expected_hex: String,
+}
+
+fn decode_hex(s: &str) -> Vec<u8> {
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn openssh_vectors() -> Vec<OpenSshVector> {
+    serde_json::from_str(include_str!("data/openssh.json")).unwrap()
+}
+
+#[test]
+fn test_openssh_vectors() {
+    let vectors = openssh_vectors();
+    assert!(vectors.len() >= 20);
+    for v in vectors.iter() {
+        let expected = decode_hex(&v.expected_hex);
+        assert_eq!(expected.len(), v.out_len);
+
+        let mut out = vec![0u8; v.out_len];
+        bcrypt_pbkdf(&v.passphrase, &decode_hex(&v.salt_hex), v.rounds, &mut out);
+        assert_eq!(out, expected, "passphrase {:?}", v.passphrase);
+    }
+}
@@ -0,0 +1,35 @@
+//! A crude canary for order-of-magnitude slowdowns of the bcrypt hash, such
+//! as an expensive debug assertion left in its inner loop. It is not a
+//! benchmark, and is ignored by default because timings depend on the
+//! machine; run it with `cargo test --features internals -- --ignored`.
+#![cfg(feature="internals")]
+extern crate bcrypt_pbkdf;
+extern crate sha2;
+
+use std::time::{Duration, Instant};
+
+use bcrypt_pbkdf::internals::bhash;
+use sha2::{Digest, Sha512};
+
+const CALLS: u32 = 100;
+
+/// A `bhash` call took around 5 ms in release builds and 12 ms in debug builds
+/// on the machine this was written on, so 100 calls take about 1.2 s
+/// unoptimised. The limit allows for a machine (or build) 25 times slower.
+const LIMIT: Duration = Duration::from_secs(30);
+
+#[test]
+#[ignore]
+fn test_bhash_speed() {
+    let sha2_pass = Sha512::digest(b"password");
+    let mut sha2_salt = Sha512::digest(b"salt");
+
+    let start = Instant::now();
+    for _ in 0..CALLS {
+        let out = bhash(&sha2_pass, &sha2_salt);
+        sha2_salt[..32].copy_from_slice(&out);
+    }
+    let elapsed = start.elapsed();
+
+    assert!(elapsed < LIMIT, "{} bhash calls took {:?}, limit {:?}", CALLS, elapsed, LIMIT);
+}